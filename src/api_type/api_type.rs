@@ -2,8 +2,15 @@ use serde::{Deserialize, Serialize};
 
 use crate::util::LyricRow;
 
+/// 只关心状态码的接口返回
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
+pub struct CodeRep {
+    pub code: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct Profile {
     pub nickname: String,
     pub user_id: i64,
@@ -16,16 +23,18 @@ pub struct Profile {
     pub playlist_be_subscribed_count: i32,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Account {
     pub id: i64,
+    #[serde(default)]
     pub user_name: String,
+    #[serde(default)]
     pub salt: String,
     pub vip_type: i32,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct AccountDetail {
     pub code: i32,
@@ -35,6 +44,63 @@ pub struct AccountDetail {
     pub cookie: String,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginStatusData {
+    pub code: i32,
+    pub account: Option<Account>,
+    pub profile: Option<Profile>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginStatusRep {
+    pub data: LoginStatusData,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginRefreshRep {
+    pub code: i32,
+    #[serde(default)]
+    pub cookie: Option<String>,
+}
+
+// -------- 扫码登录 ---------
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QrKey {
+    pub unikey: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QrKeyRep {
+    pub code: i32,
+    pub data: QrKey,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QrCreate {
+    pub qrurl: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QrCreateRep {
+    pub code: i32,
+    pub data: QrCreate,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QrCheckRep {
+    pub code: i32,
+    #[serde(default)]
+    pub cookie: Option<String>,
+}
+
 // -------- Playlist ---------
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -67,6 +133,13 @@ pub struct UserPlaylist {
     pub code: i32,
 }
 
+/// 新建歌单的返回
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePlaylistRep {
+    pub id: i64,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Artist {
@@ -82,6 +155,21 @@ pub struct Album {
     pub pic_url: String,
 }
 
+/// 当前用户对歌曲的播放权限
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Privilege {
+    pub id: i64,
+    #[serde(default)]
+    pub fee: i32,
+    /// 小于 0 时没有版权
+    #[serde(default)]
+    pub st: i32,
+    /// 可以播放的最高码率, 0 表示不能播放完整版本
+    #[serde(default)]
+    pub pl: i32,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Track {
@@ -90,6 +178,41 @@ pub struct Track {
     pub ar: Vec<Artist>,
     pub al: Album,
     pub dt: i32, // duration
+    /// 0/8: 免费, 1: VIP, 4: 购买专辑
+    #[serde(default)]
+    pub fee: i32,
+    /// 歌单详情中单独返回, 获取后合并到歌曲中
+    #[serde(default)]
+    pub privilege: Option<Privilege>,
+}
+
+/// 歌曲能否播放
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Availability {
+    Playable,
+    Vip,         // VIP 歌曲, 可以播放完整版本
+    Trial,       // 只能试听片段
+    Unavailable, // 没有版权或已下架
+}
+
+impl Track {
+    /// 没有播放权限信息时当作可以播放, 由获取播放地址的结果决定
+    pub fn availability(&self) -> Availability {
+        let privilege = match &self.privilege {
+            Some(privilege) => privilege,
+            None if self.fee == 1 => return Availability::Vip,
+            None => return Availability::Playable,
+        };
+        if privilege.st < 0 {
+            return Availability::Unavailable;
+        }
+        match (self.fee, privilege.pl > 0) {
+            (1, true) | (4, true) => Availability::Vip,
+            (1, false) | (4, false) => Availability::Trial,
+            (_, false) => Availability::Unavailable,
+            _ => Availability::Playable,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -103,7 +226,16 @@ pub struct PlaylistDetail {
     pub subscribed_count: i32,
     pub name: String,
     pub id: i64,
+    /// 歌曲较多时只返回前面的一部分
     pub tracks: Vec<Track>,
+    /// 歌单中全部歌曲的 id
+    #[serde(default)]
+    pub track_ids: Vec<TrackId>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TrackId {
+    pub id: i64,
 }
 
 #[derive(Deserialize, Debug)]
@@ -111,6 +243,69 @@ pub struct PlaylistDetail {
 pub struct PlaylistDetailRep {
     pub code: i32,
     pub playlist: PlaylistDetail,
+    #[serde(default)]
+    pub privileges: Vec<Privilege>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SongDetailRep {
+    pub songs: Vec<Track>,
+    #[serde(default)]
+    pub privileges: Vec<Privilege>,
+}
+
+/// 已加载的歌曲和剩下还没有加载的歌曲 id
+#[derive(Default)]
+pub struct TrackPage {
+    pub tracks: Vec<Track>,
+    pub more: Vec<i64>,
+}
+
+impl From<Vec<Track>> for TrackPage {
+    fn from(tracks: Vec<Track>) -> Self {
+        Self {
+            tracks,
+            more: vec![],
+        }
+    }
+}
+
+// ---------- 推荐 ------------
+#[derive(Deserialize, Debug)]
+pub struct RecommendSongsRep {
+    pub data: RecommendSongs,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecommendSongs {
+    pub daily_songs: Vec<Track>,
+}
+
+/// 每日推荐的歌单
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecommendPlaylist {
+    pub id: i64,
+    pub name: String,
+    /// 推荐理由
+    #[serde(default)]
+    pub copywriter: String,
+    #[serde(default)]
+    pub track_count: i32,
+    #[serde(default)]
+    pub playcount: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RecommendPlaylistsRep {
+    pub recommend: Vec<RecommendPlaylist>,
+}
+
+/// 私人 FM 只用到歌曲 id, 歌曲信息按 id 另外获取
+#[derive(Deserialize, Debug)]
+pub struct PersonalFmRep {
+    pub data: Vec<TrackId>,
 }
 
 // ---------- Music ------------
@@ -118,10 +313,36 @@ pub struct PlaylistDetailRep {
 #[serde(rename_all = "camelCase")]
 pub struct MusicDetail {
     pub id: i64,
-    pub url: String,
+    /// 没有版权或仅限 VIP 时为 null
+    pub url: Option<String>,
+    #[serde(default)]
     pub size: i32,
+    #[serde(default)]
     pub br: i32,
-    pub md5: String,
+    pub md5: Option<String>,
+    #[serde(default)]
+    pub fee: i32,
+    /// 只能试听时返回试听片段的位置
+    pub free_trial_info: Option<FreeTrialInfo>,
+}
+
+impl MusicDetail {
+    /// 没有播放地址的原因
+    pub fn unavailable_reason(&self) -> &'static str {
+        match self.fee {
+            1 => "仅限 VIP",
+            4 => "需要购买专辑",
+            _ => "没有版权",
+        }
+    }
+}
+
+/// 试听片段的开始和结束位置, 单位秒
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FreeTrialInfo {
+    pub start: f64,
+    pub end: f64,
 }
 
 #[derive(Deserialize, Debug)]
@@ -156,3 +377,195 @@ pub struct Lyric {
     pub version: i32,
     pub lyric: String,
 }
+
+// ---------- Search ------------
+/// 搜索类型
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum SearchKind {
+    Song,
+    Album,
+    Artist,
+    Playlist,
+    Lyric,
+}
+
+impl SearchKind {
+    pub const ALL: [SearchKind; 5] = [
+        SearchKind::Song,
+        SearchKind::Album,
+        SearchKind::Artist,
+        SearchKind::Playlist,
+        SearchKind::Lyric,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            SearchKind::Song => "单曲",
+            SearchKind::Album => "专辑",
+            SearchKind::Artist => "歌手",
+            SearchKind::Playlist => "歌单",
+            SearchKind::Lyric => "歌词",
+        }
+    }
+
+    /// cloudsearch 接口的 type 参数
+    pub fn code(&self) -> i32 {
+        match self {
+            SearchKind::Song => 1,
+            SearchKind::Album => 10,
+            SearchKind::Artist => 100,
+            SearchKind::Playlist => 1000,
+            SearchKind::Lyric => 1006,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchAlbum {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub size: i32,
+    pub artist: Artist,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchArtist {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub album_size: i32,
+    #[serde(default)]
+    pub music_size: i32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Creator {
+    pub nickname: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchPlaylist {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub track_count: i32,
+    #[serde(default)]
+    pub play_count: i64,
+    pub creator: Option<Creator>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSong {
+    #[serde(flatten)]
+    pub track: Track,
+    /// 搜索歌词时匹配的歌词, 不同版本的接口可能是字符串数组或 `{ txt }`
+    #[serde(default)]
+    pub lyrics: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchResult {
+    pub songs: Vec<SearchSong>,
+    pub song_count: usize,
+    pub albums: Vec<SearchAlbum>,
+    pub album_count: usize,
+    pub artists: Vec<SearchArtist>,
+    pub artist_count: usize,
+    pub playlists: Vec<SearchPlaylist>,
+    pub playlist_count: usize,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchRep {
+    #[serde(default)]
+    pub result: SearchResult,
+}
+
+/// 搜索结果中的一项
+#[derive(Debug, Clone)]
+pub enum SearchItem {
+    Song(Track),
+    Album(SearchAlbum),
+    Artist(SearchArtist),
+    Playlist(SearchPlaylist),
+    Lyric(Track, String), // 歌曲和匹配的一行歌词
+}
+
+impl SearchItem {
+    /// 单曲和歌词结果对应的歌曲
+    pub fn track(&self) -> Option<&Track> {
+        match self {
+            SearchItem::Song(track) | SearchItem::Lyric(track, _) => Some(track),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestMatch {
+    pub keyword: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SuggestResult {
+    pub all_match: Vec<SuggestMatch>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestRep {
+    #[serde(default)]
+    pub result: SuggestResult,
+}
+
+/// 专辑详情
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AlbumRep {
+    pub songs: Vec<Track>,
+}
+
+/// 歌手的热门歌曲
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtistSongsRep {
+    pub hot_songs: Vec<Track>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Availability, MusicDetail, Track};
+
+    #[test]
+    fn it_models_unavailable_tracks() {
+        let detail: MusicDetail = serde_json::from_str(
+            r#"{"id":1,"url":null,"size":0,"br":0,"md5":null,"fee":1,"freeTrialInfo":null}"#,
+        )
+        .unwrap();
+        assert!(detail.url.is_none());
+        assert_eq!(detail.unavailable_reason(), "仅限 VIP");
+
+        let track = |fee: i32, st: i32, pl: i32| -> Track {
+            serde_json::from_str(&format!(
+                r#"{{"id":1,"name":"a","ar":[],"al":{{"id":1,"name":"b","picUrl":""}},"dt":1000,
+                "fee":{},"privilege":{{"id":1,"fee":{},"st":{},"pl":{}}}}}"#,
+                fee, fee, st, pl
+            ))
+            .unwrap()
+        };
+        assert_eq!(track(8, 0, 128000).availability(), Availability::Playable);
+        assert_eq!(track(1, 0, 320000).availability(), Availability::Vip);
+        assert_eq!(track(1, 0, 0).availability(), Availability::Trial);
+        assert_eq!(track(0, -200, 0).availability(), Availability::Unavailable);
+    }
+}
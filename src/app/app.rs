@@ -1,17 +1,67 @@
-use crate::{api_type::api_type::{AccountDetail, Playlist, PlaylistDetail, Track}, util::{LyricRow, parse_rows}};
-use crate::util::network;
+use crate::{api_type::api_type::{AccountDetail, Availability, Playlist, PlaylistDetail, SearchItem, Track, TrackPage}, util::{LyricRow, parse_rows}};
+use crate::config::Config;
+use crate::cover;
+use crate::downloader::Progress;
+use crate::session;
+use crate::player::{self, Fade, Media, Position, TrackSource, FADE};
+use crate::util::network::{self, Api};
 use crate::util::StatefulList;
+use crate::worker::{CancelToken, TaskDone, TaskKind, TaskOutput, Worker};
 use crossterm::event::KeyCode;
-use reqwest::blocking::Client;
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
-use crate::app::Input;
+use rodio::{OutputStream, OutputStreamHandle, Sink};
+use crate::app::{
+    Captcha, Dialog, Input, LoginMode, PlayMode, QrLogin, QrState, Queue, Recommend, Search,
+    ShuffleOrder,
+    Upcoming, UpcomingFrom,
+};
+use crate::app::recommend::{TAB_FM, TAB_PLAYLISTS, TAB_SONGS};
+use crate::Event;
 use image::RgbaImage;
-use anyhow;
+use crate::util::error::{ApiError, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 提示信息显示的时间
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+/// 同时显示的提示信息数量
+const MAX_TOASTS: usize = 3;
+/// 封面缩略图的边长
+const COVER_SIZE: u32 = 300;
+/// 内存中最多保留的封面数
+const COVER_LIMIT: usize = 32;
+/// 选中的歌曲距离列表末尾多少首时加载下一批
+const LOAD_AHEAD: usize = 10;
+/// 私人 FM 剩下几首时获取下一批
+const FM_AHEAD: usize = 2;
+/// 最后一次调整顺序后多久保存
+const ORDER_DELAY: Duration = Duration::from_secs(1);
+/// 停止输入后多久获取搜索建议
+const SUGGEST_DELAY: Duration = Duration::from_millis(300);
+
+/// 侧边栏中歌单的分组
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PlaylistSection {
+    Liked,     // 我喜欢的音乐
+    Created,   // 创建的歌单
+    Collected, // 收藏的歌单
+}
+
+impl PlaylistSection {
+    pub fn title(&self) -> &'static str {
+        match self {
+            PlaylistSection::Liked => "我喜欢的音乐",
+            PlaylistSection::Created => "创建的歌单",
+            PlaylistSection::Collected => "收藏的歌单",
+        }
+    }
+}
 
 #[derive(PartialEq, Eq)]
 pub enum Focus {
     Playlist,
     Track,
+    Queue,
 }
 
 #[derive(PartialEq, Eq)]
@@ -21,23 +71,107 @@ pub enum Route {
     Home,          // 主页面
     Search,        // 搜索页面
     MusicAnalysis, // 音乐播放详情页面
+    Recommend,     // 每日推荐页面
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum ToastLevel {
+    Info,
+    Error,
+}
+
+/// 提示信息
+pub struct Toast {
+    pub level: ToastLevel,
+    pub message: String,
+    pub expire_at: Instant,
 }
 
 pub struct PlayerController {
     pub is_pause: bool,
-    pub seek: i32,
+    /// 播放位置, 由实际播放的采样数计算
+    pub position: Position,
+    /// 正在播放的歌曲的音量渐变
+    pub fade: Fade,
     pub volume: f32,
+    pub mode: PlayMode,
+    pub shuffle: ShuffleOrder,
+}
+
+impl PlayerController {
+    /// 已播放的时间
+    pub fn played(&self) -> Duration {
+        self.position.get()
+    }
+
+    /// 下一首的序号, `auto` 表示当前歌曲播放结束后自动切换, 返回 None 时停止播放
+    pub fn next_index(&mut self, current: usize, len: usize, auto: bool) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        match self.mode {
+            PlayMode::Shuffle => self.shuffle.next(len, current),
+            PlayMode::LoopOne if auto => Some(current),
+            PlayMode::Sequential if auto && current + 1 >= len => None,
+            PlayMode::StopAfterCurrent if auto => None,
+            _ => Some((current + 1) % len),
+        }
+    }
+
+    /// 自动切换时的下一首序号, 不改变随机播放的位置, 单曲循环和播完停止不从歌单中取
+    pub fn peek_next_index(&mut self, current: usize, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        match self.mode {
+            PlayMode::Shuffle => self.shuffle.peek(len, current),
+            PlayMode::LoopOne | PlayMode::StopAfterCurrent => None,
+            PlayMode::Sequential if current + 1 >= len => None,
+            _ => Some((current + 1) % len),
+        }
+    }
+
+    /// 上一首的序号, 随机播放时返回上一首实际播放的歌曲
+    pub fn previous_index(&mut self, current: usize, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        let previous = (current + len - 1) % len;
+        match self.mode {
+            PlayMode::Shuffle => self.shuffle.previous(len, current).or(Some(previous)),
+            _ => Some(previous),
+        }
+    }
+}
+
+/// 已经加入 `Sink` 等待播放的下一首
+struct Prefetched {
+    media: Arc<Media>,
+    position: Position,
+    fade: Fade,
+    /// 交叉淡入淡出时下一首使用单独的 `Sink`, 否则直接加在当前 `Sink` 后面
+    sink: Option<Sink>,
+    lrc: Vec<LyricRow>,
 }
 
 pub struct App<'a> {
     // 路由
     pub route: Route,
     pub inputs: Vec<Input>,
+    pub login_mode: LoginMode,
+    pub captcha: Captcha,
+    pub qr_login: Option<QrLogin>,
     
     // 系统运行总时间
     pub system_tick: u64,
 
-    pub client: Client,
+    pub config: Config,
+    pub api: Api,
+    pub worker: Worker,
+    /// 正在下载的歌曲名和下载进度
+    pub download: Option<(String, Arc<Progress>)>,
+    /// 是否显示缓存信息
+    pub show_cache: bool,
 
     pub title: &'a str,
     pub should_quit: bool,
@@ -51,17 +185,49 @@ pub struct App<'a> {
 
     pub selected_playlist_index: usize,
     pub current_playlist: Option<PlaylistDetail>,
+    /// 歌曲列表显示的歌单 id, 显示专辑和歌手的歌曲时为空
+    shown_playlist: Option<i64>,
+    /// 歌曲列表中还没有加载的歌曲 id, 滚动到列表末尾时继续加载
+    more_tracks: Vec<i64>,
+    /// 歌单管理的弹窗
+    pub dialog: Option<Dialog>,
+    /// 等待保存的歌曲顺序, 连续调整时只保存最后的顺序
+    pending_order: Option<(i64, Vec<i64>, Instant)>,
     pub current_playlist_track_state: StatefulList<Track>,
-    pub cookie: String,
     pub likelist: Vec<i64>,
+    /// 正在提交的喜欢/取消喜欢, 失败时回滚
+    pending_like: Option<(i64, bool)>,
 
     pub focus: Focus,
 
     current_track_idx: usize,
     current_tracks: Vec<Track>,
+    /// 播放列表中还没有加载的歌曲 id, 快播完时继续加载
+    playing_more: Vec<i64>,
+    /// 正在播放的歌曲, 可能来自播放队列
+    playing: Option<Track>,
+    pub queue: Queue,
+
+    pub search: Search,
+    pub recommend: Recommend,
+    /// 私人 FM 模式, 播放列表为 FM 获取的歌曲, 快播完时继续获取
+    pub fm: bool,
+    /// 私人 FM 的下一首还没有获取到, 获取后立即播放
+    fm_waiting: bool,
 
     pub handle: &'a OutputStreamHandle,
     pub sink: Sink,
+    /// 切歌或交叉淡入淡出时正在淡出的歌曲, 静音后停止
+    fading: Vec<(Sink, Fade)>,
+    /// 正在播放的歌曲数据, 跳转时重新解码
+    pub media: Option<Arc<Media>>,
+    /// 正在跳转的目标位置, 连续跳转时以此为准
+    pub pending_seek: Option<Duration>,
+    /// 计划预加载的下一首
+    upcoming: Option<Upcoming>,
+    prefetched: Option<Prefetched>,
+    /// 自动切换到的歌曲正在加载时为连续跳过的歌曲数, 加载失败时继续切换下一首
+    auto_skipped: Option<usize>,
 
     pub player_controller: PlayerController,
 
@@ -70,26 +236,34 @@ pub struct App<'a> {
     pub lrc: Vec<LyricRow>,
 
     pub temp_img: RgbaImage,
+    /// 已加载的封面, 按图片地址保存
+    covers: HashMap<String, Arc<RgbaImage>>,
+
+    // 提示信息
+    pub toasts: Vec<Toast>,
 }
 
 impl<'a> App<'a> {
-    pub fn new(title: &'a str, handle: &'a OutputStreamHandle, image: RgbaImage) -> Self {
+    pub fn new(
+        title: &'a str,
+        handle: &'a OutputStreamHandle,
+        image: RgbaImage,
+        config: Config,
+        api: Api,
+        worker: Worker,
+    ) -> Self {
         Self {
             route: Route::Login,
-            inputs: vec![
-                Input::default()
-                    .title("👦用户名".to_string())
-                    .placeholder("请输入用户名".to_string())
-                    .block(true),
-                Input::default()
-                    .title("🔒密码".to_string())
-                    .placeholder("请输入密码".to_string())
-                    .is_password(true)
-                    .block(true)
-                    
-            ],
+            inputs: LoginMode::Password.inputs(),
+            login_mode: LoginMode::Password,
+            captcha: Captcha::default(),
+            qr_login: None,
             system_tick: 0,
-            client: Client::builder().cookie_store(true).build().unwrap(),
+            config,
+            api,
+            worker,
+            download: None,
+            show_cache: false,
             title,
             should_quit: false,
             progress: 0.0,
@@ -100,128 +274,575 @@ impl<'a> App<'a> {
             current_playlist: Option::None,
             current_playlist_track_state: StatefulList::with_items(vec![]),
             selected_playlist_index: 0,
-            cookie: String::new(),
+            shown_playlist: None,
+            more_tracks: vec![],
+            dialog: None,
+            pending_order: None,
             likelist: vec![],
+            pending_like: None,
 
             focus: Focus::Playlist,
 
             current_tracks: vec![],
+            playing_more: vec![],
             current_track_idx: 0,
+            playing: None,
+            queue: Queue::new(),
+
+            search: Search::new(),
+            recommend: Recommend::new(),
+            fm: false,
+            fm_waiting: false,
 
             handle,
             sink: Sink::try_new(handle).unwrap(),
+            fading: vec![],
+            media: None,
+            pending_seek: None,
+            upcoming: None,
+            prefetched: None,
+            auto_skipped: None,
             player_controller: PlayerController {
                 is_pause: true,
-                seek: 0,
+                position: Position::default(),
+                fade: Fade::paused(),
                 volume: 1.0,
+                mode: PlayMode::LoopList,
+                shuffle: ShuffleOrder::default(),
             },
 
             lrc: vec![],
             show_lrc: true,
 
             temp_img: image,
+            covers: HashMap::new(),
+
+            toasts: vec![],
         }
     }
 
-    pub fn set_playlists(&mut self, list: Vec<Playlist>) {
+    /// 按分组排序后显示, 同一分组中保持接口返回的顺序
+    pub fn set_playlists(&mut self, mut list: Vec<Playlist>) {
+        list.sort_by_key(|p| self.playlist_section(p));
         self.playlists_state = StatefulList::with_items(list);
-        self.playlists_state.next();
+        if !self.playlists_state.items.is_empty() {
+            self.playlists_state.next();
+        }
+        self.fetch_playlist_cover();
+    }
+
+    pub fn playlist_section(&self, playlist: &Playlist) -> PlaylistSection {
+        if playlist.special_type == 5 {
+            PlaylistSection::Liked
+        } else if Some(playlist.creator.user_id) == self.user_id() {
+            PlaylistSection::Created
+        } else {
+            PlaylistSection::Collected
+        }
+    }
+
+    /// 每个分组在歌单列表中的起始位置和歌单数, 不包含空的分组
+    pub fn playlist_sections(&self) -> Vec<(PlaylistSection, usize, usize)> {
+        let mut sections: Vec<(PlaylistSection, usize, usize)> = vec![];
+        for (i, playlist) in self.playlists_state.items.iter().enumerate() {
+            let section = self.playlist_section(playlist);
+            match sections.last_mut() {
+                Some((last, _, len)) if *last == section => *len += 1,
+                _ => sections.push((section, i, 1)),
+            }
+        }
+        sections
+    }
+
+    pub fn set_current_playlist(&mut self, page: TrackPage) {
+        self.worker.cancel(TaskKind::PlaylistPage);
+        self.current_playlist_track_state = StatefulList::with_items(page.tracks);
+        self.more_tracks = page.more;
+        if !self.current_playlist_track_state.items.is_empty() {
+            self.current_playlist_track_state.next();
+        }
+        // 第一批歌曲太少时直接加载下一批
+        if self.current_playlist_track_state.items.len() <= LOAD_AHEAD {
+            self.load_more_tracks();
+        }
+    }
+
+    /// 已加载的歌曲数和歌曲总数
+    pub fn track_count(&self) -> (usize, usize) {
+        let loaded = self.current_playlist_track_state.items.len();
+        (loaded, loaded + self.more_tracks.len())
     }
 
-    pub fn set_current_playlist(&mut self, list: Vec<Track>) {
-        self.current_playlist_track_state = StatefulList::with_items(list);
-        self.current_playlist_track_state.next();
+    /// 选中下一首, 还有歌曲没有加载时不回到开头, 接近末尾时加载下一批
+    fn select_next_track(&mut self) {
+        let list = &mut self.current_playlist_track_state;
+        let len = list.items.len();
+        let wait_more =
+            !self.more_tracks.is_empty() && list.state.selected() == Some(len.saturating_sub(1));
+        if len > 0 && !wait_more {
+            list.next();
+        }
+        let selected = self.current_playlist_track_state.state.selected().unwrap_or(0);
+        if selected + LOAD_AHEAD >= len {
+            self.load_more_tracks();
+        }
+    }
+
+    fn load_more_tracks(&mut self) {
+        if self.more_tracks.is_empty() || self.worker.is_running(TaskKind::PlaylistPage) {
+            return;
+        }
+        let ids: Vec<i64> = self.more_tracks.iter().take(network::TRACK_PAGE).cloned().collect();
+        self.spawn(TaskKind::PlaylistPage, move |api, _| {
+            let tracks = network::get_song_detail(&api, &ids)?;
+            Ok(TaskOutput::PlaylistPage { ids, tracks })
+        });
+    }
+
+    /// 加载播放列表的下一批, 和歌曲列表相同时一起加载
+    fn load_more_playing(&mut self) {
+        if self.playing_more.is_empty() || self.worker.is_running(TaskKind::PlayingPage) {
+            return;
+        }
+        if self.playing_more == self.more_tracks {
+            return self.load_more_tracks();
+        }
+        let ids: Vec<i64> = self.playing_more.iter().take(network::TRACK_PAGE).cloned().collect();
+        self.spawn(TaskKind::PlayingPage, move |api, _| {
+            let tracks = network::get_song_detail(&api, &ids)?;
+            Ok(TaskOutput::PlaylistPage { ids, tracks })
+        });
+    }
+
+    /// 加载到的歌曲加入请求了这些 id 的歌曲列表和播放列表
+    fn on_more_tracks(&mut self, ids: Vec<i64>, tracks: Vec<Track>) {
+        if self.more_tracks.starts_with(&ids) {
+            self.more_tracks.drain(..ids.len());
+            self.current_playlist_track_state.items.extend(tracks.iter().cloned());
+        }
+        if self.playing_more.starts_with(&ids) {
+            self.playing_more.drain(..ids.len());
+            self.current_tracks.extend(tracks);
+            self.refresh_prefetch();
+        }
     }
 
     pub fn on_up(&mut self) {
+        if self.route == Route::Search {
+            return self.search.select_previous();
+        }
+        if self.route == Route::Recommend {
+            return self.recommend.select_previous();
+        }
         match self.focus {
-            Focus::Playlist => self.playlists_state.previous(),
+            Focus::Playlist => {
+                self.playlists_state.previous();
+                self.fetch_playlist_cover();
+            }
             Focus::Track => self.current_playlist_track_state.previous(),
+            Focus::Queue => self.queue.select_previous(),
         }
     }
 
     pub fn on_down(&mut self) {
+        if self.route == Route::Search {
+            return self.search.select_next();
+        }
+        if self.route == Route::Recommend {
+            return self.recommend.select_next();
+        }
         match self.focus {
-            Focus::Playlist => self.playlists_state.next(),
-            Focus::Track => self.current_playlist_track_state.next(),
+            Focus::Playlist => {
+                self.playlists_state.next();
+                self.fetch_playlist_cover();
+            }
+            Focus::Track => self.select_next_track(),
+            Focus::Queue => self.queue.select_next(),
         }
     }
 
     pub fn on_right(&mut self) {
+        if self.route == Route::Search {
+            self.search.tabs.next();
+            return self.load_search(0);
+        }
+        if self.route == Route::Recommend {
+            return self.recommend.tabs.next();
+        }
         match self.focus {
             Focus::Playlist => self.focus = Focus::Track,
-            Focus::Track => self.focus = Focus::Playlist,
+            Focus::Track if !self.queue.is_empty() => self.focus = Focus::Queue,
+            _ => self.focus = Focus::Playlist,
         }
     }
 
     pub fn on_left(&mut self) {
-        // self.tabs.previous();
+        if self.route == Route::Search {
+            self.search.tabs.previous();
+            return self.load_search(0);
+        }
+        if self.route == Route::Recommend {
+            return self.recommend.tabs.previous();
+        }
         match self.focus {
-            Focus::Playlist => self.focus = Focus::Track,
+            Focus::Playlist if !self.queue.is_empty() => self.focus = Focus::Queue,
             Focus::Track => self.focus = Focus::Playlist,
+            _ => self.focus = Focus::Track,
         }
     }
 
     pub fn on_enter(&mut self) {
+        if self.route == Route::Search {
+            return self.open_search_result();
+        }
+        if self.route == Route::Recommend {
+            return self.open_recommend();
+        }
         if self.route != Route::Home {
             return;
         }
         match self.focus {
             Focus::Playlist => {
-                let _ = network::get_playlist_detail(self);
-                self.on_left();
+                self.load_playlist_detail();
+                self.focus = Focus::Track;
+            }
+            // 立即播放队列中选中的歌曲
+            Focus::Queue => {
+                if let Some(track) = self.queue.remove_selected() {
+                    self.play_track(track);
+                }
+                self.leave_empty_queue();
             }
             Focus::Track => {
-                let track = self.current_playlist_track_state.items[self
-                    .current_playlist_track_state
-                    .state
-                    .selected()
-                    .unwrap_or(0)]
-                .clone();
-                self.current_track_idx = self
-                    .current_playlist_track_state
-                    .state
-                    .selected()
-                    .unwrap_or(0);
+                let list = &self.current_playlist_track_state;
+                let selected = match list.state.selected().filter(|i| *i < list.items.len()) {
+                    Some(i) => i,
+                    None => return,
+                };
+                let track = list.items[selected].clone();
+                self.current_track_idx = selected;
                 self.playing_playlist_idx = self.playlists_state.state.selected().unwrap_or(0);
-                self.download_and_play_track(track);
                 self.current_tracks = self.current_playlist_track_state.items.clone();
+                self.playing_more = self.more_tracks.clone();
+                self.stop_fm();
+                self.play_track(track);
             }
         }
     }
 
-    fn download_and_play_track(&mut self, track: Track) {
-        let id = track.id;
-        let track_name = track.name.clone();
-        if let Ok(v) = network::get_music_detail(vec![id], self) {
-            if v.len() > 0 {
-                let music_url = v[0].url.clone();
-                let vec: Vec<&str> = music_url.rsplitn(2, ".").collect();
-                let file_type = vec.first().unwrap_or(&&"mp3");
-                let current_music_path = network::download_music(
-                    music_url.as_ref(),
-                    format!("{}.{}", track_name, file_type).as_ref(),
-                );
-                if let Some(path) = current_music_path {
-                    self.fetch_lrc(id);
-                    self.play_music(&path);
-                }
-            }
+    /// 在后台获取歌曲地址并开始缓冲, 缓冲到可以解码时开始播放
+    fn play_track(&mut self, track: Track) {
+        self.auto_skipped = None;
+        self.clear_prefetch();
+        self.worker.cancel(TaskKind::Seek);
+        self.pending_seek = None;
+        self.spawn(TaskKind::Play, move |api, _| {
+            let media = Arc::new(network::open_track(&api, &track)?);
+            let source = media.open_at(Duration::from_secs(0), track_duration(&track))?;
+            Ok(TaskOutput::Ready {
+                track,
+                media,
+                source: Box::new(source),
+            })
+        });
+    }
+
+    /// 跳转到指定位置, 在后台重新解码
+    fn seek_to(&mut self, target: Duration) {
+        let (media, duration) = match (self.media.clone(), self.playing_duration()) {
+            (Some(media), Some(duration)) => (media, duration),
+            _ => return,
+        };
+        // 跳到结尾时留一点, 避免直接切到下一首
+        let target = target.min(duration.checked_sub(Duration::from_secs(1)).unwrap_or_default());
+        self.pending_seek = Some(target);
+        self.spawn(TaskKind::Seek, move |_, _| {
+            Ok(TaskOutput::Seeked(Box::new(media.open_at(target, duration)?)))
+        });
+    }
+
+    /// 向前或向后跳转 `secs` 秒
+    fn seek_by(&mut self, secs: i64) {
+        let current = self
+            .pending_seek
+            .filter(|_| self.worker.is_running(TaskKind::Seek))
+            .unwrap_or_else(|| self.player_controller.played());
+        let target = if secs >= 0 {
+            current + Duration::from_secs(secs as u64)
+        } else {
+            current
+                .checked_sub(Duration::from_secs(secs.unsigned_abs()))
+                .unwrap_or_default()
+        };
+        self.seek_to(target);
+    }
+
+    /// 跳转到歌曲的百分比位置
+    fn seek_percent(&mut self, percent: u32) {
+        if let Some(duration) = self.playing_duration() {
+            self.seek_to(duration * percent / 100);
+        }
+    }
+
+    /// 下载选中的歌曲到缓存目录, 同时只下载一首
+    fn download_selected(&mut self) {
+        if self.route != Route::Home || self.focus != Focus::Track {
+            return;
         }
+        let selected = self.current_playlist_track_state.state.selected().unwrap_or(0);
+        let track = match self.current_playlist_track_state.items.get(selected) {
+            Some(track) => track.clone(),
+            None => return,
+        };
+        let progress = Arc::new(Progress::default());
+        self.download = Some((track.name.clone(), progress.clone()));
+        self.spawn(TaskKind::Download, move |api, cancel| {
+            let path = network::download_track(&api, &track, progress, &cancel)?;
+            Ok(TaskOutput::Downloaded { track, path })
+        });
     }
 
-    fn play_music(&mut self, path: &String) {
-        let file = std::fs::File::open(path).unwrap();
-        let buf = std::io::BufReader::new(file);
-        // 停止当前播放
-        if !self.sink.empty() {
-            self.sink.stop();
-            self.sink = Sink::try_new(self.handle).unwrap();
+    /// 清空歌曲缓存, 只在显示缓存信息时可用
+    fn clear_cache(&mut self) {
+        if !self.show_cache {
+            return;
         }
+        self.api.cache().clear();
+        cover::clear(&self.config);
+        self.notify("缓存已清空");
+    }
+
+    fn play_music(&mut self, source: TrackSource) {
         self.player_controller.is_pause = false;
-        self.player_controller.seek = 0;
-        self.sink.set_volume(self.player_controller.volume);
-        self.sink.append(Decoder::new(buf).unwrap());
+        self.replace_source(source);
+    }
+
+    /// 用新的 source 替换正在播放的内容, 保持暂停状态
+    fn replace_source(&mut self, source: TrackSource) {
+        // 重建 Sink 后之前加入的下一首也会被丢弃, 已经加载的数据可以复用
+        let reuse = self.clear_prefetch();
+        // 当前歌曲淡出后停止, 新的歌曲淡入
+        let sink = self.new_sink();
+        let old = std::mem::replace(&mut self.sink, sink);
+        if !old.empty() && !self.player_controller.is_pause {
+            self.player_controller.fade.fade_out(FADE);
+            self.fading.push((old, self.player_controller.fade.clone()));
+        }
+        let fade = Fade::paused();
+        if !self.player_controller.is_pause {
+            fade.resume(FADE);
+        }
+        self.player_controller.position = source.position();
+        self.player_controller.fade = fade.clone();
+        self.append_source(&self.sink, source, fade);
+        let upcoming = self.peek_upcoming();
+        self.prefetch(upcoming, reuse);
+    }
+
+    /// 和当前音量相同的新 `Sink`
+    fn new_sink(&self) -> Sink {
+        let sink = Sink::try_new(self.handle).unwrap();
+        sink.set_volume(self.player_controller.volume);
+        sink
+    }
+
+    /// 加上音量渐变和结束通知后交给 `sink` 播放
+    fn append_source(&self, sink: &Sink, source: TrackSource, fade: Fade) {
+        sink.append(player::faded(source.on_end(self.end_notifier()), fade));
+    }
+
+    fn crossfade(&self) -> Duration {
+        Duration::from_secs(self.config.crossfade)
+    }
+
+    /// 当前歌曲剩余的时间
+    fn remaining(&self) -> Duration {
+        if self.player_controller.position.is_finished() {
+            return Duration::from_secs(0);
+        }
+        self.playing_duration()
+            .unwrap_or_default()
+            .checked_sub(self.player_controller.played())
+            .unwrap_or_default()
+    }
+
+    /// 正在播放的时长, 只能试听时为试听片段的时长
+    pub fn playing_duration(&self) -> Option<Duration> {
+        let track = self.current_playing_track()?;
+        let trial = self.media.as_ref().and_then(|m| m.trial());
+        Some(trial.unwrap_or_else(|| track_duration(track)))
+    }
+
+    /// 只能试听时提示
+    fn notify_trial(&mut self) {
+        let trial = self.media.as_ref().and_then(|m| m.trial());
+        if let (Some(trial), Some(track)) = (trial, self.current_playing_track()) {
+            let message = format!("《{}》只能试听 {} 秒", track.name, trial.as_secs());
+            self.notify(message);
+        }
+    }
+
+    /// 当前歌曲快要结束时开始交叉淡入淡出
+    fn start_crossfade(&mut self) {
+        let ready = matches!(&self.prefetched, Some(p) if p.sink.is_some());
+        if ready && self.remaining() <= self.crossfade() {
+            self.switch_to_prefetched();
+        }
+    }
+
+    /// 歌曲播放结束时通知主线程
+    fn end_notifier(&self) -> impl FnOnce() + Send + 'static {
+        let tx = self.worker.sender();
+        move || {
+            let _ = tx.send(Event::SourceEnd);
+        }
+    }
+
+    /// 当前歌曲播放完后自动播放的下一首
+    fn peek_upcoming(&mut self) -> Option<Upcoming> {
+        let playing = self.playing.clone()?;
+        match self.player_controller.mode {
+            PlayMode::LoopOne => {
+                return Some(Upcoming {
+                    track: playing,
+                    from: UpcomingFrom::Repeat,
+                })
+            }
+            PlayMode::StopAfterCurrent => return None,
+            _ => {}
+        }
+        if let Some(track) = self.queue.front() {
+            return Some(Upcoming {
+                track: track.clone(),
+                from: UpcomingFrom::Queue,
+            });
+        }
+        let len = self.current_tracks.len();
+        // 私人 FM 按获取的顺序播放, 不受随机播放影响
+        let index = if self.fm {
+            Some(self.current_track_idx + 1).filter(|i| *i < len)?
+        } else {
+            self.player_controller
+                .peek_next_index(self.current_track_idx, len)?
+        };
+        // 无法播放的歌曲等当前歌曲结束后再跳过
+        if self.current_tracks[index].availability() == Availability::Unavailable {
+            return None;
+        }
+        Some(Upcoming {
+            track: self.current_tracks[index].clone(),
+            from: UpcomingFrom::Playlist(index),
+        })
+    }
+
+    /// 下一首改变时重新预加载, 如修改了播放队列或播放模式
+    fn refresh_prefetch(&mut self) {
+        let upcoming = self.peek_upcoming();
+        if upcoming == self.upcoming {
+            return;
+        }
+        let reuse = self.clear_prefetch();
+        self.prefetch(upcoming, reuse);
+    }
+
+    /// 取消预加载, 返回已经加载的数据以便复用
+    fn clear_prefetch(&mut self) -> Option<(Upcoming, Arc<Media>)> {
+        self.worker.cancel(TaskKind::Prefetch);
+        let upcoming = self.upcoming.take();
+        let prefetched = self.prefetched.take()?;
+        prefetched.position.cancel();
+        Some((upcoming?, prefetched.media))
+    }
+
+    /// 在后台加载下一首和歌词, 完成后加入 `Sink`
+    fn prefetch(&mut self, upcoming: Option<Upcoming>, reuse: Option<(Upcoming, Arc<Media>)>) {
+        let upcoming = match upcoming {
+            Some(upcoming) => upcoming,
+            None => return,
+        };
+        let media = match upcoming.from {
+            UpcomingFrom::Repeat => self.media.clone(),
+            _ => reuse
+                .filter(|(u, _)| u.track.id == upcoming.track.id)
+                .map(|(_, media)| media),
+        };
+        let track = upcoming.track.clone();
+        let repeat = upcoming.from == UpcomingFrom::Repeat;
+        self.upcoming = Some(upcoming);
+        self.spawn(TaskKind::Prefetch, move |api, _| {
+            let media = match media {
+                Some(media) => media,
+                None => Arc::new(network::open_track(&api, &track)?),
+            };
+            let source = media.open_at(Duration::from_secs(0), track_duration(&track))?;
+            // 单曲循环时沿用当前的歌词
+            let lrc = if repeat {
+                vec![]
+            } else {
+                network::get_lyric_by_music_id(&track.id, &api)
+                    .map(|lyric| parse_rows(lyric.lyric.as_ref()))
+                    .unwrap_or_default()
+            };
+            Ok(TaskOutput::Prefetched {
+                id: track.id,
+                media,
+                source: Box::new(source),
+                lrc,
+            })
+        });
+    }
+
+    /// 正在播放的歌曲结束, 预加载的下一首已经开始播放时同步界面状态
+    pub fn on_source_end(&mut self) {
+        if self.prefetched.is_some() && self.player_controller.position.is_finished() {
+            self.switch_to_prefetched();
+        }
+    }
+
+    fn switch_to_prefetched(&mut self) {
+        let (upcoming, prefetched) = match (self.upcoming.take(), self.prefetched.take()) {
+            (Some(upcoming), Some(prefetched)) => (upcoming, prefetched),
+            _ => return,
+        };
+        if let Some(sink) = prefetched.sink {
+            // 当前歌曲在剩余时间内淡出, 下一首同时淡入
+            let old = std::mem::replace(&mut self.sink, sink);
+            self.player_controller.fade.fade_out(self.remaining());
+            self.fading.push((old, self.player_controller.fade.clone()));
+            if !self.player_controller.is_pause {
+                prefetched.fade.resume(self.crossfade());
+            }
+        } else if self.player_controller.is_pause {
+            // 暂停淡出的过程中当前歌曲已经结束
+            prefetched.fade.pause(FADE);
+        }
+        match upcoming.from {
+            UpcomingFrom::Queue => {
+                if self.queue.front().map(|t| t.id) == Some(upcoming.track.id) {
+                    self.queue.pop();
+                    self.leave_empty_queue();
+                }
+            }
+            UpcomingFrom::Playlist(index) => {
+                if self.player_controller.mode == PlayMode::Shuffle {
+                    let len = self.current_tracks.len();
+                    self.player_controller.shuffle.next(len, self.current_track_idx);
+                }
+                self.current_track_idx = index;
+            }
+            UpcomingFrom::Repeat => {}
+        }
+        if upcoming.from != UpcomingFrom::Repeat {
+            self.fetch_cover(TaskKind::Cover, upcoming.track.al.pic_url.clone());
+            self.worker.cancel(TaskKind::Lyric);
+            self.lrc = prefetched.lrc;
+            self.playing = Some(upcoming.track);
+        }
+        self.media = Some(prefetched.media);
+        self.player_controller.position = prefetched.position;
+        self.player_controller.fade = prefetched.fade;
+        self.notify_trial();
+        self.refresh_prefetch();
     }
 
 
@@ -233,42 +854,97 @@ impl<'a> App<'a> {
             ' ' => {
                 self.on_space();
             }
-            '-' => {
-                let volumn = self.sink.volume();
-                if volumn > 0.0 {
-                    self.sink.set_volume(volumn - 0.01);
-                }
+            '-' => self.volume_down(),
+            '=' => self.volume_up(),
+            // 快退/快进 5 秒
+            ',' if self.is_player_page() => self.seek_by(-5),
+            '.' if self.is_player_page() => self.seek_by(5),
+            // 快退/快进 30 秒
+            '<' if self.is_player_page() => self.seek_by(-30),
+            '>' if self.is_player_page() => self.seek_by(30),
+            // 跳转到 0% ~ 90%
+            '0'..='9' if self.is_player_page() => {
+                self.seek_percent(c.to_digit(10).unwrap_or(0) * 10)
             }
-            '=' => {
-                let volumn = self.sink.volume();
-                if volumn < 1.0 {
-                    self.sink.set_volume(volumn + 0.01);
-                }
+            // 加入播放队列/下一首播放
+            'a' if self.route == Route::Home && self.focus == Focus::Track => {
+                self.enqueue_selected(false)
+            }
+            'n' if self.route == Route::Home && self.focus == Focus::Track => {
+                self.enqueue_selected(true)
+            }
+            'd' | 'K' | 'J' | 'c' if self.route == Route::Home && self.focus == Focus::Queue => {
+                self.on_queue_key(c)
             }
+            // 新建、重命名、删除歌单
+            'n' | 'r' | 'd' if self.route == Route::Home && self.focus == Focus::Playlist => {
+                self.on_playlist_key(c)
+            }
+            // 从歌单移除歌曲, 上移/下移
+            'd' | 'K' | 'J' if self.route == Route::Home && self.focus == Focus::Track => {
+                self.on_playlist_track_key(c)
+            }
+            // 加入歌单, 歌曲列表获得焦点时为选中的歌曲, 否则为正在播放的歌曲
+            'p' if self.is_player_page() => self.pick_playlist(),
+            '/' | '[' | ']' | 'a' | 'n' if self.route == Route::Search => self.on_search_key(c),
+            'a' | 'n' | 'r' if self.route == Route::Recommend => self.on_recommend_key(c),
+            // 私人 FM 中不喜欢当前歌曲并跳过
+            't' if self.fm => self.fm_trash(),
             _ => {}
         }
     }
 
+    /// 取消正在进行的请求, 加载页取消时回到登录页
+    pub fn on_esc(&mut self) {
+        if self.worker.pending().is_empty() {
+            // 没有进行中的请求时从搜索页和播放详情页返回
+            match self.route {
+                Route::Search => self.toggle_search(),
+                Route::MusicAnalysis => self.toggle_now_playing(),
+                Route::Recommend => self.toggle_recommend(),
+                _ => {}
+            }
+            return;
+        }
+        // 提交喜欢等后台请求继续执行, 失败时仍然回滚
+        self.worker.cancel_visible();
+        if self.route == Route::Loading {
+            self.reset_user();
+        }
+        self.notify("已取消");
+    }
+
     pub fn on_space(&mut self) {
         self.player_controller.is_pause = !self.player_controller.is_pause;
         if self.player_controller.is_pause {
-            self.sink.pause();
+            // 正在淡出的上一首直接停止
+            self.fading.clear();
+            self.player_controller.fade.pause(FADE);
         } else {
-            self.sink.play();
+            self.player_controller.fade.resume(FADE);
         }
     }
 
     pub fn on_tick(&mut self) {
         self.system_tick += 1;
+        let now = Instant::now();
+        self.toasts.retain(|t| t.expire_at > now);
+        if self.route == Route::Login
+            && self.login_mode == LoginMode::Qr
+            && !self.worker.is_running(TaskKind::QrCheck)
+        {
+            self.check_qr_login();
+        }
+        self.save_order();
+        self.fading.retain(|(_, fade)| !fade.is_silent());
         if self.player_controller.is_pause {
             return;
         }
+        self.start_crossfade();
+        self.on_source_end();
         if self.sink.empty() {
             self.player_controller.is_pause = true;
-            self.on_ctrl_key(KeyCode::Right);
-        }
-        if !self.player_controller.is_pause {
-            self.player_controller.seek += 1;
+            self.on_track_end();
         }
     }
 
@@ -288,10 +964,35 @@ impl<'a> App<'a> {
     }
 
     pub fn current_playing_track(&self) -> Option<&Track> {
-        if self.current_tracks.is_empty() {
-            Option::None
+        self.playing.as_ref()
+    }
+
+    /// 操作的对象, 歌曲列表获得焦点时为选中的歌曲, 否则为正在播放的歌曲
+    fn target_track(&self) -> Option<&Track> {
+        if self.route == Route::Home && self.focus == Focus::Track {
+            let list = &self.current_playlist_track_state;
+            list.state.selected().and_then(|i| list.items.get(i))
+        } else {
+            self.current_playing_track()
+        }
+    }
+
+    /// 私人 FM 中接下来播放的歌曲
+    pub fn fm_upcoming(&self) -> &[Track] {
+        if !self.fm {
+            return &[];
+        }
+        self.current_tracks
+            .get(self.current_track_idx + 1..)
+            .unwrap_or(&[])
+    }
+
+    /// 播放模式, 私人 FM 中按获取的顺序播放
+    pub fn play_mode_title(&self) -> &'static str {
+        if self.fm {
+            "私人 FM"
         } else {
-            Option::Some(&self.current_tracks[self.current_track_idx])
+            self.player_controller.mode.title()
         }
     }
 
@@ -307,8 +1008,28 @@ impl<'a> App<'a> {
 /// 播放控制
 impl<'a> App<'a> {
     pub fn input_on_key(&mut self, key: KeyCode) {
-        self.inputs.iter_mut().for_each(|input| input.on_key(key));
+        if self.dialog.is_some() {
+            return self.on_dialog_key(key);
+        }
+        match self.route {
+            Route::Login => self.inputs.iter_mut().for_each(|input| input.on_key(key)),
+            Route::Search if self.search.input.focus => self.on_search_input(key),
+            _ => {}
+        }
+    }
+
+    /// 正在输入时按键只交给输入框
+    pub fn is_editing(&self) -> bool {
+        if self.dialog.is_some() {
+            return true;
+        }
+        match self.route {
+            Route::Login => self.inputs.iter().any(|input| input.focus),
+            Route::Search => self.search.input.focus,
+            _ => false,
+        }
     }
+
     pub fn on_ctrl_key(&mut self, code: KeyCode) {
         match code {
             // 上一首
@@ -327,52 +1048,256 @@ impl<'a> App<'a> {
             KeyCode::Char('h') => self.show_help(),
             // 切换选中的输入框
             KeyCode::Char('i') => self.focus_next_input(),
+            // 退出登录
+            KeyCode::Char('o') => self.logout(),
+            // 切换登录方式
+            KeyCode::Char('t') => self.switch_login_mode(),
+            // 刷新登录二维码
+            KeyCode::Char('r') => self.refresh_qr_login(),
+            // 发送短信验证码
+            KeyCode::Char('s') => self.send_captcha(),
+            // 切换播放模式
+            KeyCode::Char('p') => self.switch_play_mode(),
+            // 切换音质
+            KeyCode::Char('b') => self.switch_quality(),
+            // 打开/关闭搜索页
+            KeyCode::Char('f') => self.toggle_search(),
+            // 打开/关闭播放详情页
+            KeyCode::Char('n') => self.toggle_now_playing(),
+            // 打开/关闭每日推荐
+            KeyCode::Char('g') => self.toggle_recommend(),
+            // 下载选中的歌曲
+            KeyCode::Char('w') => self.download_selected(),
+            // 打开/关闭缓存信息
+            KeyCode::Char('k') => self.show_cache = !self.show_cache,
+            // 清空缓存
+            KeyCode::Char('x') => self.clear_cache(),
             // 登录
-            KeyCode::Enter => {
-                self.login();
-            }
+            KeyCode::Enter => self.login(),
             _ => {}
         }
     }
 
     // 播放上一首
     pub fn previous_track(&mut self) {
+        if self.fm {
+            if let Some(index) = self.current_track_idx.checked_sub(1) {
+                self.play_index(index);
+            }
+            return;
+        }
         let len = self.current_tracks.len();
-        if len > 0 {
-            self.current_track_idx = (self.current_track_idx + len - 1) % len;
-            self.download_and_play_track(self.current_tracks[self.current_track_idx].clone());
+        if let Some(index) = self.player_controller.previous_index(self.current_track_idx, len) {
+            self.play_index(index);
         }
     }
 
     // 播放下一首
     pub fn next_track(&mut self) {
+        if let Some(track) = self.queue.pop() {
+            self.play_track(track);
+            self.leave_empty_queue();
+            return;
+        }
+        if let Some(index) = self.next_playable_index(false) {
+            self.play_index(index);
+        }
+    }
+
+    /// 歌单中的下一首, 跳过已知无法播放的歌曲并提示
+    fn next_playable_index(&mut self, auto: bool) -> Option<usize> {
+        if self.fm {
+            return self.next_fm_index();
+        }
         let len = self.current_tracks.len();
-        if len > 0 {
-            self.current_track_idx = (self.current_track_idx + 1) % len;
-            self.download_and_play_track(self.current_tracks[self.current_track_idx].clone());
+        let mut current = self.current_track_idx;
+        let mut skipped = vec![];
+        let mut next = None;
+        for _ in 0..len {
+            let index = match self.player_controller.next_index(current, len, auto) {
+                Some(index) => index,
+                None => break,
+            };
+            let track = &self.current_tracks[index];
+            if track.availability() != Availability::Unavailable {
+                next = Some(index);
+                break;
+            }
+            skipped.push(format!("《{}》", track.name));
+            current = index;
+        }
+        match skipped.len() {
+            0 => {}
+            1 => self.notify(format!("{}没有版权或已下架, 已跳过", skipped[0])),
+            n => self.notify(format!("{}等 {} 首歌曲没有版权或已下架, 已跳过", skipped[0], n)),
+        }
+        next
+    }
+
+    // 当前歌曲播放结束, 按播放模式切换或停止
+    fn on_track_end(&mut self) {
+        let skipped = self.auto_skipped.take();
+        self.play_after_end();
+        // 记录自动切换, 加载失败时继续跳到下一首
+        if self.worker.is_running(TaskKind::Play) {
+            self.auto_skipped = Some(skipped.map_or(0, |n| n + 1));
+        }
+    }
+
+    fn play_after_end(&mut self) {
+        match self.player_controller.mode {
+            PlayMode::LoopOne => {
+                if let Some(track) = self.playing.clone() {
+                    self.play_track(track);
+                }
+            }
+            PlayMode::StopAfterCurrent => {}
+            _ => {
+                if let Some(track) = self.queue.pop() {
+                    self.play_track(track);
+                    self.leave_empty_queue();
+                    return;
+                }
+                if let Some(index) = self.next_playable_index(true) {
+                    self.play_index(index);
+                }
+            }
+        }
+    }
+
+    // 选中的歌曲加入播放队列, `next` 为 true 时作为下一首播放
+    fn enqueue_selected(&mut self, next: bool) {
+        let selected = self.current_playlist_track_state.state.selected().unwrap_or(0);
+        if let Some(track) = self.current_playlist_track_state.items.get(selected) {
+            self.enqueue_track(track.clone(), next);
+        }
+    }
+
+    fn enqueue_track(&mut self, track: Track, next: bool) {
+        let message = if next {
+            format!("《{}》将在下一首播放", track.name)
+        } else {
+            format!("《{}》已加入播放队列", track.name)
+        };
+        if next {
+            self.queue.play_next(track);
+        } else {
+            self.queue.enqueue(track);
+        }
+        self.refresh_prefetch();
+        self.notify(message);
+    }
+
+    // 队列清空后焦点回到歌曲列表
+    fn leave_empty_queue(&mut self) {
+        if self.queue.is_empty() && self.focus == Focus::Queue {
+            self.focus = Focus::Track;
+        }
+    }
+
+    // 播放队列的操作
+    fn on_queue_key(&mut self, c: char) {
+        match c {
+            // 移除
+            'd' => {
+                self.queue.remove_selected();
+            }
+            // 上移/下移
+            'K' => self.queue.move_selected(true),
+            'J' => self.queue.move_selected(false),
+            // 清空
+            'c' => self.queue.clear(),
+            _ => {}
+        }
+        self.leave_empty_queue();
+        self.refresh_prefetch();
+    }
+
+    fn play_index(&mut self, index: usize) {
+        self.current_track_idx = index;
+        self.play_track(self.current_tracks[index].clone());
+        // 私人 FM 快播完时提前获取下一批
+        if self.fm && index + FM_AHEAD >= self.current_tracks.len() {
+            self.fetch_fm();
+        }
+        // 歌单快播完或随机播放时加载剩下的歌曲
+        let shuffle = self.player_controller.mode == PlayMode::Shuffle;
+        if shuffle || index + LOAD_AHEAD >= self.current_tracks.len() {
+            self.load_more_playing();
+        }
+    }
+
+    // 切换播放模式
+    pub fn switch_play_mode(&mut self) {
+        self.player_controller.mode = self.player_controller.mode.next();
+        self.refresh_prefetch();
+        self.notify(format!("播放模式: {}", self.player_controller.mode.title()));
+    }
+
+    // 切换音质, 从下一首开始生效
+    pub fn switch_quality(&mut self) {
+        let quality = self.config.quality.next();
+        self.config.quality = quality;
+        self.api.config.quality = quality;
+        // 已经预加载的下一首按新的音质重新加载
+        self.clear_prefetch();
+        let upcoming = self.peek_upcoming();
+        self.prefetch(upcoming, None);
+        self.notify(format!("音质: {}", quality.title()));
+    }
+
+    /// 设置音量, 预加载的下一首和正在淡出的上一首同时调整
+    fn set_volume(&mut self, volume: f32) {
+        self.sink.set_volume(volume);
+        if let Some(sink) = self.prefetched.as_ref().and_then(|p| p.sink.as_ref()) {
+            sink.set_volume(volume);
+        }
+        for (sink, _) in &self.fading {
+            sink.set_volume(volume);
         }
     }
 
     // 音量加
     pub fn volume_up(&mut self) {
-        let volume = self.sink.volume();
-        if volume < 1.0 {
-            self.sink.set_volume(volume + 0.01);
-            self.player_controller.volume += 0.01;
-        }
+        let volume = (self.player_controller.volume + 0.01).min(1.0);
+        self.player_controller.volume = volume;
+        self.set_volume(volume);
     }
 
     // 音量减
     pub fn volume_down(&mut self) {
-        let volume = self.sink.volume();
-        if volume > 0.0 {
-            self.sink.set_volume(volume - 0.01);
-            self.player_controller.volume -= 0.01;
+        let volume = (self.player_controller.volume - 0.01).max(0.0);
+        self.player_controller.volume = volume;
+        self.set_volume(volume);
+    }
+
+    // 喜欢/取消喜欢音乐, 歌曲列表获得焦点时为选中的歌曲, 否则为正在播放的歌曲
+    pub fn like(&mut self) {
+        let id = match self.target_track() {
+            Some(track) => track.id,
+            None => return,
+        };
+        if self.pending_like.is_some() {
+            self.notify("正在提交上一次操作, 请稍后再试");
+            return;
         }
+        // 先更新界面, 请求失败时再改回来
+        let like = !self.is_liked(&id);
+        self.set_liked(id, like);
+        self.pending_like = Some((id, like));
+        self.spawn(TaskKind::Like, move |api, _| {
+            network::like(&api, id, like)?;
+            Ok(TaskOutput::Liked { id, like })
+        });
     }
 
-    // 喜欢音乐
-    pub fn like(&mut self) {}
+    fn set_liked(&mut self, id: i64, like: bool) {
+        if !like {
+            self.likelist.retain(|i| *i != id);
+        } else if !self.likelist.contains(&id) {
+            self.likelist.push(id);
+        }
+    }
 
     // 打开/关闭歌词
     pub fn show_lrc(&mut self) {
@@ -407,41 +1332,1114 @@ impl<'a> App<'a> {
         self.route = route;
     }
 
-    pub fn login(&mut self) -> anyhow::Result<()>{
+    /// 使用当前登录方式和输入框的内容登录
+    pub fn login(&mut self) {
+        if self.route != Route::Login {
+            return;
+        }
+        let login: fn(&Api, &str, &str) -> Result<AccountDetail> = match self.login_mode {
+            LoginMode::Password => network::login_cellphone,
+            LoginMode::Captcha => network::login_captcha,
+            LoginMode::Email => network::login_email,
+            LoginMode::Qr => return,
+        };
+        let account = self.inputs[0].val.clone();
+        let secret = self.inputs[1].val.clone();
         self.goto_page(Route::Loading);
-        network::login(self)?;
-        network::get_like_list(self)?;
-        network::playlists(self)?;
-        network::get_playlist_detail(self)?;
+        self.spawn(TaskKind::Login, move |api, _| {
+            Ok(TaskOutput::Account(login(&api, &account, &secret)?))
+        });
+    }
 
-        self.goto_page(Route::Home);
+    /// 使用上次保存的登录信息登录, 登录失效时回到登录页
+    pub fn restore_session(&mut self, account: AccountDetail) {
+        self.goto_page(Route::Loading);
+        self.spawn(TaskKind::Login, move |mut api, _| {
+            Ok(TaskOutput::Account(network::restore_login(&mut api, account)?))
+        });
+    }
 
-        Ok(())
+    /// 退出登录并删除保存的登录信息
+    pub fn logout(&mut self) {
+        if self.userinfo.is_none() {
+            return;
+        }
+        let _ = session::clear();
+        // reset_user 会清除 cookie, 先保留一份用于请求退出接口
+        let api = self.api.clone();
+        self.reset_user();
+        self.worker.spawn(TaskKind::Logout, api, |api, _| {
+            network::logout(&api)?;
+            Ok(TaskOutput::LoggedOut)
+        });
+        self.notify("已退出登录");
     }
 
-    
-    fn fetch_lrc(&mut self, id: i64)  {
-        if let Ok(lyric) = network::get_lyric_by_music_id(&id, self) {
-            self.lrc = parse_rows(lyric.lyric.as_ref());
+    /// 切换登录方式, 切换到扫码登录时生成二维码
+    pub fn switch_login_mode(&mut self) {
+        if self.route != Route::Login {
+            return;
+        }
+        self.login_mode = self.login_mode.next();
+        self.inputs = self.login_mode.inputs();
+        let pending = self.qr_login.as_ref().map(|q| q.is_pending()).unwrap_or(false);
+        if self.login_mode == LoginMode::Qr && !pending {
+            self.refresh_qr_login();
         }
     }
 
-    pub fn get_avaiable_lrc_row(&self) -> &str {
-        if self.lrc.is_empty() {
-            "暂无歌词"
+    /// 发送短信验证码, 倒计时结束前不能重复发送
+    pub fn send_captcha(&mut self) {
+        if self.route != Route::Login
+            || self.login_mode != LoginMode::Captcha
+            || self.captcha.countdown() > 0
+            || self.worker.is_running(TaskKind::Captcha)
+        {
+            return;
+        }
+        if self.inputs[0].val.is_empty() {
+            self.notify("请先输入手机号");
+            return;
+        }
+        let phone = self.inputs[0].val.clone();
+        self.spawn(TaskKind::Captcha, move |api, _| {
+            network::send_captcha(&api, &phone)?;
+            Ok(TaskOutput::CaptchaSent)
+        });
+    }
+
+    /// 重新获取登录二维码
+    pub fn refresh_qr_login(&mut self) {
+        if self.route != Route::Login || self.login_mode != LoginMode::Qr {
+            return;
+        }
+        self.worker.cancel(TaskKind::QrCheck);
+        self.spawn(TaskKind::QrCode, |api, _| {
+            let key = network::qr_key(&api)?;
+            let url = network::qr_create(&api, &key)?;
+            Ok(TaskOutput::QrCode { key, url })
+        });
+    }
+
+    /// 轮询二维码状态
+    fn check_qr_login(&mut self) {
+        let key = match self.qr_login.as_ref().filter(|q| q.is_pending()) {
+            Some(qr) => qr.key.clone(),
+            None => return,
+        };
+        self.spawn(TaskKind::QrCheck, move |api, _| {
+            Ok(TaskOutput::QrCheck(network::qr_check(&api, &key)?))
+        });
+    }
+
+    /// 加载喜欢的音乐、歌单和第一个歌单的歌曲
+    fn load_user_data(&mut self) {
+        let user_id = match self.userinfo.as_ref() {
+            Some(account) => account.account.id,
+            None => return,
+        };
+        self.spawn(TaskKind::UserData, move |api, _| {
+            let likelist = network::get_like_list(&api, user_id)?;
+            let playlists = network::playlists(&api, user_id)?;
+            let page = match playlists.first() {
+                Some(playlist) => network::get_playlist_detail(&api, playlist.id)?,
+                None => TrackPage::default(),
+            };
+            Ok(TaskOutput::UserData {
+                likelist,
+                playlists,
+                page,
+            })
+        });
+    }
+
+    /// 加载选中歌单的歌曲
+    fn load_playlist_detail(&mut self) {
+        let selected = self.playlists_state.state.selected().unwrap_or(0);
+        let id = match self.playlists_state.items.get(selected) {
+            Some(playlist) => playlist.id,
+            None => return,
+        };
+        self.spawn(TaskKind::PlaylistDetail, move |api, _| {
+            Ok(TaskOutput::PlaylistDetail {
+                id: Some(id),
+                page: network::get_playlist_detail(&api, id)?,
+            })
+        });
+    }
+
+    /// 可以跳转播放位置的页面
+    fn is_player_page(&self) -> bool {
+        matches!(self.route, Route::Home | Route::MusicAnalysis)
+    }
+
+    pub fn toggle_now_playing(&mut self) {
+        match self.route {
+            Route::Home => self.goto_page(Route::MusicAnalysis),
+            Route::MusicAnalysis => self.goto_page(Route::Home),
+            _ => {}
+        }
+    }
+
+    pub fn toggle_search(&mut self) {
+        match self.route {
+            Route::Home => {
+                self.search.input.focus = true;
+                self.search.reset_hints();
+                self.goto_page(Route::Search);
+            }
+            Route::Search => {
+                self.search.input.focus = false;
+                self.goto_page(Route::Home);
+            }
+            _ => {}
+        }
+    }
+
+    /// 搜索框中的按键, 上下键选择搜索历史或建议
+    fn on_search_input(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Enter => {
+                if let Some(keywords) = self.search.selected_hint().cloned() {
+                    self.search.input.val = keywords;
+                }
+                self.search.input.focus = false;
+                self.submit_search();
+            }
+            KeyCode::Esc => self.search.input.focus = false,
+            KeyCode::Up => self.search.select_previous_hint(),
+            KeyCode::Down => self.search.select_next_hint(),
+            // 删除选中的搜索历史
+            KeyCode::Delete => self.search.remove_selected_history(),
+            key => {
+                let before = self.search.input.val.clone();
+                self.search.input.on_key(key);
+                if self.search.input.val != before {
+                    self.search.reset_hints();
+                    self.suggest();
+                }
+            }
+        }
+    }
+
+    /// 停止输入一段时间后在后台获取搜索建议, 新的输入会取消之前的请求
+    fn suggest(&mut self) {
+        let keywords = self.search.input.val.trim().to_string();
+        if keywords.is_empty() {
+            self.worker.cancel(TaskKind::Suggest);
+            return;
+        }
+        self.spawn(TaskKind::Suggest, move |api, cancel| {
+            std::thread::sleep(SUGGEST_DELAY);
+            // 已被新的输入取消时结果会被丢弃, 不再请求
+            let items = if cancel.is_cancelled() {
+                vec![]
+            } else {
+                network::search_suggest(&api, &keywords)?
+            };
+            Ok(TaskOutput::Suggest { keywords, items })
+        });
+    }
+
+    /// 使用输入框的关键词搜索
+    fn submit_search(&mut self) {
+        let keywords = self.search.input.val.trim().to_string();
+        if keywords.is_empty() {
+            return;
+        }
+        self.worker.cancel(TaskKind::Suggest);
+        self.search.remember(&keywords);
+        self.search.reset_hints();
+        self.search.keywords = keywords;
+        self.load_search(0);
+    }
+
+    /// 加载当前类型的第 `page` 页搜索结果
+    fn load_search(&mut self, page: usize) {
+        if self.search.keywords.is_empty() {
+            return;
+        }
+        let keywords = self.search.keywords.clone();
+        let kind = self.search.kind();
+        self.spawn(TaskKind::Search, move |api, _| {
+            let (items, total) = network::search(&api, &keywords, kind, page)?;
+            Ok(TaskOutput::Search {
+                kind,
+                page,
+                items,
+                total,
+            })
+        });
+    }
+
+    // 搜索页的操作
+    fn on_search_key(&mut self, c: char) {
+        match c {
+            // 输入关键词
+            '/' => {
+                self.search.input.focus = true;
+                self.search.reset_hints();
+            }
+            // 上一页/下一页
+            '[' if self.search.page > 0 => self.load_search(self.search.page - 1),
+            ']' if self.search.page + 1 < self.search.pages() => {
+                self.load_search(self.search.page + 1)
+            }
+            // 加入播放队列/下一首播放
+            'a' | 'n' => {
+                if let Some(track) = self.search.selected().and_then(|i| i.track()).cloned() {
+                    self.enqueue_track(track, c == 'n');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 播放选中的歌曲, 或打开专辑、歌手、歌单的歌曲列表
+    fn open_search_result(&mut self) {
+        let selected = self.search.results.state.selected().unwrap_or(0);
+        let item = match self.search.results.items.get(selected) {
+            Some(item) => item.clone(),
+            None => return,
+        };
+        match item {
+            SearchItem::Song(_) | SearchItem::Lyric(..) => {
+                // 这一页的歌曲作为播放列表
+                self.stop_fm();
+                self.playing_more.clear();
+                self.current_tracks = self
+                    .search
+                    .results
+                    .items
+                    .iter()
+                    .filter_map(|i| i.track().cloned())
+                    .collect();
+                self.play_index(selected);
+            }
+            SearchItem::Album(album) => {
+                self.open_tracks(None, move |api| {
+                    Ok(network::get_album_tracks(api, album.id)?.into())
+                })
+            }
+            SearchItem::Artist(artist) => {
+                self.open_tracks(None, move |api| {
+                    Ok(network::get_artist_tracks(api, artist.id)?.into())
+                })
+            }
+            SearchItem::Playlist(playlist) => {
+                let id = playlist.id;
+                self.open_tracks(Some(id), move |api| network::get_playlist_detail(api, id))
+            }
+        }
+    }
+
+    pub fn toggle_recommend(&mut self) {
+        match self.route {
+            Route::Home => {
+                self.goto_page(Route::Recommend);
+                if !self.recommend.loaded {
+                    self.load_recommend();
+                }
+            }
+            Route::Recommend => self.goto_page(Route::Home),
+            _ => {}
+        }
+    }
+
+    fn load_recommend(&mut self) {
+        self.spawn(TaskKind::Recommend, move |api, _| {
+            Ok(TaskOutput::Recommend {
+                songs: network::get_recommend_songs(&api)?,
+                playlists: network::get_recommend_playlists(&api)?,
+            })
+        });
+    }
+
+    fn on_recommend_key(&mut self, c: char) {
+        match c {
+            // 重新加载
+            'r' => self.load_recommend(),
+            // 加入播放队列/下一首播放
+            'a' | 'n' if self.recommend.tabs.index == TAB_SONGS => {
+                if let Some(i) = self.recommend.selected_song() {
+                    self.enqueue_track(self.recommend.songs.items[i].clone(), c == 'n');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 播放选中的推荐歌曲, 打开选中的推荐歌单, 或开启私人 FM
+    fn open_recommend(&mut self) {
+        match self.recommend.tabs.index {
+            TAB_SONGS => {
+                if let Some(i) = self.recommend.selected_song() {
+                    self.stop_fm();
+                    self.current_tracks = self.recommend.songs.items.clone();
+                    self.playing_more.clear();
+                    self.play_index(i);
+                }
+            }
+            TAB_PLAYLISTS => {
+                if let Some(id) = self.recommend.selected_playlist().map(|p| p.id) {
+                    self.open_tracks(Some(id), move |api| network::get_playlist_detail(api, id))
+                }
+            }
+            TAB_FM => self.start_fm(),
+            _ => {}
+        }
+    }
+
+    /// 开启私人 FM, 获取到歌曲后开始播放
+    fn start_fm(&mut self) {
+        self.fm = true;
+        self.fm_waiting = true;
+        self.current_tracks.clear();
+        self.playing_more.clear();
+        self.current_track_idx = 0;
+        self.clear_prefetch();
+        self.fetch_fm();
+        self.goto_page(Route::MusicAnalysis);
+    }
+
+    fn stop_fm(&mut self) {
+        self.fm = false;
+        self.fm_waiting = false;
+        self.worker.cancel(TaskKind::Fm);
+    }
+
+    fn fetch_fm(&mut self) {
+        if self.worker.is_running(TaskKind::Fm) {
+            return;
+        }
+        self.spawn(TaskKind::Fm, move |api, _| {
+            Ok(TaskOutput::Fm(network::get_personal_fm(&api)?))
+        });
+    }
+
+    /// 私人 FM 的下一首, 还没有获取到时等获取后再播放
+    fn next_fm_index(&mut self) -> Option<usize> {
+        let next = self.current_track_idx + 1;
+        if next < self.current_tracks.len() {
+            return Some(next);
+        }
+        self.fm_waiting = true;
+        self.fetch_fm();
+        None
+    }
+
+    fn on_fm_tracks(&mut self, tracks: Vec<Track>) {
+        if !self.fm {
+            return;
+        }
+        if tracks.is_empty() && self.fm_waiting {
+            self.stop_fm();
+            return self.notify("私人 FM 暂时没有更多歌曲");
+        }
+        let first = self.current_tracks.is_empty();
+        self.current_tracks.extend(tracks);
+        if !self.fm_waiting {
+            return self.refresh_prefetch();
+        }
+        self.fm_waiting = false;
+        let next = if first { 0 } else { self.current_track_idx + 1 };
+        if next < self.current_tracks.len() {
+            self.play_index(next);
+        }
+    }
+
+    /// 不喜欢正在播放的歌曲, 以后不再推荐, 并跳到下一首
+    fn fm_trash(&mut self) {
+        let track = match self.current_playing_track() {
+            Some(track) => track.clone(),
+            None => return,
+        };
+        self.spawn(TaskKind::FmTrash, move |api, _| {
+            network::fm_trash(&api, track.id)?;
+            Ok(TaskOutput::FmTrashed(track.name))
+        });
+        self.next_track();
+    }
+
+    /// 回到主页并在歌曲列表中显示加载的歌曲
+    fn open_tracks<F>(&mut self, id: Option<i64>, load: F)
+    where
+        F: FnOnce(&Api) -> Result<TrackPage> + Send + 'static,
+    {
+        match self.route {
+            Route::Search => self.toggle_search(),
+            _ => self.goto_page(Route::Home),
+        }
+        self.focus = Focus::Track;
+        self.spawn(TaskKind::PlaylistDetail, move |api, _| {
+            Ok(TaskOutput::PlaylistDetail {
+                id,
+                page: load(&api)?,
+            })
+        });
+    }
+
+    /// 清空用户相关的状态并回到登录页
+    fn reset_user(&mut self) {
+        self.clear_prefetch();
+        self.worker.cancel_all();
+        self.sink.stop();
+        self.sink = self.new_sink();
+        self.fading.clear();
+        self.player_controller.is_pause = true;
+        self.player_controller.position = Position::default();
+        self.player_controller.fade = Fade::paused();
+        self.media = None;
+        self.pending_seek = None;
+        self.userinfo = None;
+        self.api.reset();
+        self.likelist.clear();
+        self.pending_like = None;
+        self.shown_playlist = None;
+        self.dialog = None;
+        self.recommend = Recommend::new();
+        self.fm = false;
+        self.fm_waiting = false;
+        self.set_playlists(vec![]);
+        self.set_current_playlist(TrackPage::default());
+        self.current_tracks.clear();
+        self.playing_more.clear();
+        self.current_track_idx = 0;
+        self.playing = None;
+        self.queue.clear();
+        self.lrc.clear();
+        self.inputs = self.login_mode.inputs();
+        self.goto_page(Route::Login);
+    }
+
+    
+    /// 加载封面, 已经加载过的不重复加载
+    fn fetch_cover(&mut self, kind: TaskKind, url: String) {
+        if url.is_empty() || self.covers.contains_key(&url) {
+            return;
+        }
+        self.spawn(kind, move |api, _| {
+            let image = network::get_cover(&api, &url, COVER_SIZE)?;
+            Ok(TaskOutput::Cover { url, image })
+        });
+    }
+
+    fn fetch_playlist_cover(&mut self) {
+        let list = &self.playlists_state;
+        let url = match list.state.selected().and_then(|i| list.items.get(i)) {
+            Some(playlist) => playlist.cover_img_url.clone(),
+            None => return,
+        };
+        self.fetch_cover(TaskKind::PlaylistCover, url);
+    }
+
+    fn cover(&self, url: &str) -> Option<Arc<RgbaImage>> {
+        self.covers.get(url).cloned()
+    }
+
+    /// 正在播放的歌曲的专辑封面
+    pub fn playing_cover(&self) -> Option<Arc<RgbaImage>> {
+        self.cover(&self.current_playing_track()?.al.pic_url)
+    }
+
+    /// 选中的歌单的封面
+    pub fn playlist_cover(&self) -> Option<Arc<RgbaImage>> {
+        let i = self.playlists_state.state.selected()?;
+        self.cover(&self.playlists_state.items.get(i)?.cover_img_url)
+    }
+
+    fn fetch_lrc(&mut self, id: i64)  {
+        self.lrc.clear();
+        self.spawn(TaskKind::Lyric, move |api, _| {
+            let lyric = network::get_lyric_by_music_id(&id, &api)?;
+            Ok(TaskOutput::Lyric {
+                id,
+                rows: parse_rows(lyric.lyric.as_ref()),
+            })
+        });
+    }
+
+    /// 显示提示信息
+    pub fn notify<S: Into<String>>(&mut self, message: S) {
+        self.push_toast(ToastLevel::Info, message.into());
+    }
+
+    /// 显示错误信息, 登录失效时回到登录页
+    pub fn notify_error(&mut self, err: ApiError) {
+        if let ApiError::AuthExpired = err {
+            let _ = session::clear();
+            self.reset_user();
+        }
+        self.push_toast(ToastLevel::Error, err.to_string());
+    }
+
+    fn push_toast(&mut self, level: ToastLevel, message: String) {
+        if self.toasts.len() >= MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+        self.toasts.push(Toast {
+            level,
+            message,
+            expire_at: Instant::now() + TOAST_DURATION,
+        });
+    }
+
+    pub fn get_avaiable_lrc_row(&self) -> &str {
+        match self.current_lrc_index() {
+            Some(index) => self.lrc[index].content.as_ref(),
+            None => "暂无歌词",
+        }
+    }
+
+    /// 当前播放到的歌词行, 第一行开始之前为第一行
+    pub fn current_lrc_index(&self) -> Option<usize> {
+        if self.lrc.is_empty() {
+            return None;
+        }
+        let played = self.player_controller.played().as_secs() as i32;
+        let index = self.lrc.iter().rposition(|row| row.start < played);
+        Some(index.unwrap_or(0))
+    }
+
+}
+
+// 歌单管理
+
+impl<'a> App<'a> {
+    fn user_id(&self) -> Option<i64> {
+        self.userinfo.as_ref().map(|u| u.profile.user_id)
+    }
+
+    /// 自己创建的歌单才能修改, "我喜欢的音乐"只能通过喜欢/取消喜欢修改
+    fn is_editable(&self, playlist: &Playlist) -> bool {
+        self.playlist_section(playlist) == PlaylistSection::Created
+    }
+
+    fn selected_playlist(&self) -> Option<&Playlist> {
+        self.playlists_state
+            .items
+            .get(self.playlists_state.state.selected()?)
+    }
+
+    /// 歌曲列表显示的歌单, 不能修改时为空
+    fn editable_shown_playlist(&self) -> Option<i64> {
+        let id = self.shown_playlist?;
+        self.playlists_state
+            .items
+            .iter()
+            .find(|p| p.id == id && self.is_editable(p))
+            .map(|p| p.id)
+    }
+
+    fn on_playlist_key(&mut self, c: char) {
+        if c == 'n' {
+            self.dialog = Some(Dialog::create());
+            return;
+        }
+        let (id, name) = match self.selected_playlist() {
+            Some(p) if self.is_editable(p) => (p.id, p.name.clone()),
+            Some(_) => return self.notify("只能修改自己创建的歌单"),
+            None => return,
+        };
+        self.dialog = match c {
+            'r' => Some(Dialog::rename(id, name)),
+            'd' => Some(Dialog::Delete { id, name }),
+            _ => None,
+        };
+    }
+
+    fn on_playlist_track_key(&mut self, c: char) {
+        let id = match self.editable_shown_playlist() {
+            Some(id) => id,
+            None => return self.notify("只能修改自己创建的歌单"),
+        };
+        let list = &self.current_playlist_track_state;
+        let selected = match list.state.selected().filter(|i| *i < list.items.len()) {
+            Some(i) => i,
+            None => return,
+        };
+        match c {
+            'd' => {
+                if self.playlist_busy() {
+                    return;
+                }
+                let track = self.current_playlist_track_state.items[selected].clone();
+                self.spawn(TaskKind::PlaylistEdit, move |api, _| {
+                    network::update_playlist_tracks(&api, id, &[track.id], false)?;
+                    Ok(TaskOutput::PlaylistTracks { id, track, add: false })
+                });
+            }
+            'K' | 'J' => self.move_playlist_track(id, selected, c == 'K'),
+            _ => {}
+        }
+    }
+
+    /// 先在列表中移动, 再保存新的顺序, 保存失败时重新加载歌单
+    fn move_playlist_track(&mut self, id: i64, selected: usize, up: bool) {
+        let list = &mut self.current_playlist_track_state;
+        let target = if up {
+            match selected.checked_sub(1) {
+                Some(target) => target,
+                None => return,
+            }
+        } else if selected + 1 < list.items.len() {
+            selected + 1
         } else {
-            let l = self.lrc.len();
-            let mut index = 0;
-            for i in 0..l {
-                if self.lrc[l-i-1].start < self.player_controller.seek {
-                    index = i;
-                    break;
+            return;
+        };
+        list.items.swap(selected, target);
+        list.state.select(Some(target));
+        // 还没有加载的歌曲保持原来的顺序放在后面
+        let ids: Vec<i64> = list
+            .items
+            .iter()
+            .map(|t| t.id)
+            .chain(self.more_tracks.iter().cloned())
+            .collect();
+        self.pending_order = Some((id, ids, Instant::now() + ORDER_DELAY));
+    }
+
+    /// 停止调整后保存顺序, 上一次保存完成前不发送新的请求
+    fn save_order(&mut self) {
+        match &self.pending_order {
+            Some((_, _, at)) if *at <= Instant::now() => {}
+            _ => return,
+        }
+        if self.worker.is_running(TaskKind::PlaylistOrder)
+            || self.worker.is_running(TaskKind::PlaylistEdit)
+        {
+            return;
+        }
+        if let Some((id, ids, _)) = self.pending_order.take() {
+            self.spawn(TaskKind::PlaylistOrder, move |api, _| {
+                network::reorder_playlist(&api, id, &ids)?;
+                Ok(TaskOutput::PlaylistReordered)
+            });
+        }
+    }
+
+    /// 歌单的修改依次提交, 上一个修改完成前提示稍后再试
+    fn playlist_busy(&mut self) -> bool {
+        let busy = self.worker.is_running(TaskKind::PlaylistEdit)
+            || self.worker.is_running(TaskKind::PlaylistOrder)
+            || self.pending_order.is_some();
+        if busy {
+            self.notify("正在保存歌单, 请稍后再试");
+        }
+        busy
+    }
+
+    fn reload_shown_playlist(&mut self) {
+        let id = match self.shown_playlist {
+            Some(id) => id,
+            None => return,
+        };
+        self.spawn(TaskKind::PlaylistDetail, move |api, _| {
+            Ok(TaskOutput::PlaylistDetail {
+                id: Some(id),
+                page: network::get_playlist_detail(&api, id)?,
+            })
+        });
+    }
+
+    /// 打开选择歌单的弹窗
+    fn pick_playlist(&mut self) {
+        let track = match self.target_track() {
+            Some(track) => track.clone(),
+            None => return,
+        };
+        let playlists: Vec<(i64, String)> = self
+            .playlists_state
+            .items
+            .iter()
+            .filter(|p| self.is_editable(p))
+            .map(|p| (p.id, p.name.clone()))
+            .collect();
+        if playlists.is_empty() {
+            return self.notify("没有可以加入的歌单, 在歌单列表中按 n 新建");
+        }
+        self.dialog = Some(Dialog::picker(track, playlists));
+    }
+
+    fn on_dialog_key(&mut self, key: KeyCode) {
+        let dialog = match self.dialog.as_mut() {
+            Some(dialog) => dialog,
+            None => return,
+        };
+        match key {
+            KeyCode::Esc => self.dialog = None,
+            KeyCode::Enter => self.submit_dialog(),
+            _ => match dialog {
+                Dialog::Name { input, .. } => input.on_key(key),
+                Dialog::Picker { playlists, .. } => match key {
+                    KeyCode::Up => playlists.previous(),
+                    KeyCode::Down => playlists.next(),
+                    _ => {}
+                },
+                Dialog::Delete { .. } => {}
+            },
+        }
+    }
+
+    fn submit_dialog(&mut self) {
+        if self.playlist_busy() {
+            return;
+        }
+        let user_id = self.user_id();
+        match self.dialog.take() {
+            Some(Dialog::Name { id, input }) => {
+                let name = input.val.trim().to_string();
+                if name.is_empty() {
+                    self.dialog = Some(Dialog::Name { id, input });
+                    return self.notify("歌单名不能为空");
+                }
+                match (id, user_id) {
+                    (Some(id), _) => self.spawn(TaskKind::PlaylistEdit, move |api, _| {
+                        network::rename_playlist(&api, id, &name)?;
+                        Ok(TaskOutput::PlaylistRenamed { id, name })
+                    }),
+                    (None, Some(user_id)) => self.spawn(TaskKind::PlaylistEdit, move |api, _| {
+                        let id = network::create_playlist(&api, &name)?;
+                        let playlists = network::playlists(&api, user_id)?;
+                        Ok(TaskOutput::PlaylistCreated { id, playlists })
+                    }),
+                    (None, None) => {}
                 }
             }
-            self.lrc[l - index - 1].content.as_ref()
+            Some(Dialog::Delete { id, .. }) => {
+                self.spawn(TaskKind::PlaylistEdit, move |api, _| {
+                    network::delete_playlist(&api, id)?;
+                    Ok(TaskOutput::PlaylistDeleted(id))
+                });
+            }
+            Some(Dialog::Picker { track, playlists }) => {
+                let id = match playlists.state.selected().and_then(|i| playlists.items.get(i)) {
+                    Some((id, _)) => *id,
+                    None => return,
+                };
+                self.spawn(TaskKind::PlaylistEdit, move |api, _| {
+                    network::update_playlist_tracks(&api, id, &[track.id], true)?;
+                    Ok(TaskOutput::PlaylistTracks { id, track, add: true })
+                });
+            }
+            None => {}
+        }
+    }
+
+    /// 删除歌单后更新列表, 正在显示这个歌单时清空歌曲列表
+    fn remove_playlist(&mut self, id: i64) {
+        let index = match self.playlists_state.items.iter().position(|p| p.id == id) {
+            Some(index) => index,
+            None => return,
+        };
+        let list = &mut self.playlists_state;
+        list.items.remove(index);
+        let len = list.items.len();
+        let selected = list.state.selected().filter(|_| len > 0).map(|i| i.min(len - 1));
+        list.state.select(selected);
+        if self.playing_playlist_idx > index {
+            self.playing_playlist_idx -= 1;
+        }
+        self.playing_playlist_idx = self.playing_playlist_idx.min(len.saturating_sub(1));
+        if self.shown_playlist == Some(id) {
+            self.shown_playlist = None;
+            self.set_current_playlist(TrackPage::default());
         }
+        self.fetch_playlist_cover();
+        self.notify("已删除歌单");
     }
 
+    /// 添加或移除歌曲后更新歌曲数, 正在显示这个歌单时同时更新歌曲列表
+    fn update_playlist_tracks(&mut self, id: i64, track: Track, add: bool) {
+        let name = match self.playlists_state.items.iter_mut().find(|p| p.id == id) {
+            Some(playlist) => {
+                playlist.track_count += if add { 1 } else { -1 };
+                playlist.name.clone()
+            }
+            None => String::new(),
+        };
+        let message = if add {
+            format!("《{}》已加入歌单《{}》", track.name, name)
+        } else {
+            format!("已从歌单《{}》移除《{}》", name, track.name)
+        };
+        if self.shown_playlist == Some(id) {
+            let list = &mut self.current_playlist_track_state;
+            let selected = list.state.selected();
+            list.items.retain(|t| t.id != track.id);
+            // 新加入的歌曲在歌单最前面
+            if add {
+                list.items.insert(0, track);
+            }
+            let len = list.items.len();
+            list.state
+                .select(selected.filter(|_| len > 0).map(|i| i.min(len - 1)));
+        }
+        self.notify(message);
+    }
+}
+
+// 后台任务
+
+impl<'a> App<'a> {
+    /// 提交后台任务, 任务中使用当前登录状态的 api
+    fn spawn<F>(&mut self, kind: TaskKind, task: F)
+    where
+        F: FnOnce(Api, CancelToken) -> Result<TaskOutput> + Send + 'static,
+    {
+        self.worker.spawn(kind, self.api.clone(), task);
+    }
+
+    /// 正在进行的任务的描述, 用于显示加载状态
+    pub fn pending_task_desc(&self) -> Option<&'static str> {
+        self.worker.pending().first().map(|kind| kind.desc())
+    }
+
+    /// 正在下载的歌曲名和进度, 下载被取消后不再显示
+    pub fn download_progress(&self) -> Option<(&str, f64)> {
+        match self.download.as_ref() {
+            Some((name, progress)) if self.worker.is_running(TaskKind::Download) => {
+                Some((name.as_str(), progress.ratio()))
+            }
+            _ => None,
+        }
+    }
+
+    /// 处理后台任务的结果, 已取消或被替换的任务结果直接丢弃
+    pub fn on_task(&mut self, done: TaskDone) {
+        if !self.worker.finish(&done) {
+            return;
+        }
+        match done.result {
+            Ok(output) => self.on_task_output(output),
+            Err(e) => self.on_task_error(done.kind, e),
+        }
+    }
+
+    fn on_task_output(&mut self, output: TaskOutput) {
+        match output {
+            TaskOutput::Account(account) => {
+                self.api.cookie = session::cookie_header(&account.cookie);
+                let _ = session::save(&account);
+                self.userinfo = Some(account);
+                self.load_user_data();
+            }
+            TaskOutput::CaptchaSent => {
+                self.captcha.sent();
+                self.notify("验证码已发送");
+            }
+            TaskOutput::QrCode { key, url } => {
+                self.qr_login = QrLogin::new(key, &url).ok();
+            }
+            TaskOutput::QrCheck(rep) => {
+                if let Some(state) = QrState::from_code(rep.code) {
+                    if let Some(qr) = self.qr_login.as_mut() {
+                        qr.state = state;
+                    }
+                    if state == QrState::Confirmed {
+                        let cookie = rep.cookie.unwrap_or_default();
+                        self.goto_page(Route::Loading);
+                        self.spawn(TaskKind::Login, move |mut api, _| {
+                            let account = network::login_with_cookie(&mut api, cookie, String::new())?;
+                            Ok(TaskOutput::Account(account))
+                        });
+                    }
+                }
+            }
+            TaskOutput::LoggedOut => {}
+            TaskOutput::UserData {
+                likelist,
+                playlists,
+                page,
+            } => {
+                self.likelist = likelist;
+                let shown = playlists.first().map(|p| p.id);
+                self.set_playlists(playlists);
+                // 排序后选中已经加载了歌曲的歌单
+                let index = self.playlists_state.items.iter().position(|p| Some(p.id) == shown);
+                if let Some(i) = index {
+                    self.playlists_state.state.select(Some(i));
+                    self.fetch_playlist_cover();
+                }
+                self.shown_playlist = shown;
+                self.set_current_playlist(page);
+                self.goto_page(Route::Home);
+            }
+            TaskOutput::PlaylistDetail { id, page } => {
+                self.shown_playlist = id;
+                self.set_current_playlist(page);
+            }
+            TaskOutput::PlaylistPage { ids, tracks } => self.on_more_tracks(ids, tracks),
+            TaskOutput::PlaylistCreated { id, playlists } => {
+                self.set_playlists(playlists);
+                if let Some(i) = self.playlists_state.items.iter().position(|p| p.id == id) {
+                    self.playlists_state.state.select(Some(i));
+                    self.fetch_playlist_cover();
+                }
+                self.notify("已新建歌单");
+            }
+            TaskOutput::PlaylistRenamed { id, name } => {
+                if let Some(p) = self.playlists_state.items.iter_mut().find(|p| p.id == id) {
+                    p.name = name;
+                }
+                self.notify("已重命名歌单");
+            }
+            TaskOutput::PlaylistDeleted(id) => self.remove_playlist(id),
+            TaskOutput::PlaylistTracks { id, track, add } => {
+                self.update_playlist_tracks(id, track, add)
+            }
+            TaskOutput::PlaylistReordered => {}
+            TaskOutput::Recommend { songs, playlists } => {
+                self.recommend.set_results(songs, playlists)
+            }
+            TaskOutput::Fm(tracks) => self.on_fm_tracks(tracks),
+            TaskOutput::FmTrashed(name) => self.notify(format!("《{}》以后不会再推荐", name)),
+            TaskOutput::Liked { id, like } => {
+                self.pending_like = None;
+                self.set_liked(id, like);
+                self.notify(if like { "已添加到我喜欢的音乐" } else { "已取消喜欢" });
+            }
+            TaskOutput::Cover { url, image } => {
+                // 缩略图已经缓存在磁盘上, 内存中的太多时只保留正在播放的
+                if self.covers.len() >= COVER_LIMIT {
+                    let playing = self.current_playing_track().map(|t| t.al.pic_url.clone());
+                    self.covers.retain(|u, _| Some(u) == playing.as_ref());
+                }
+                self.covers.insert(url, Arc::new(image));
+            }
+            TaskOutput::Suggest { keywords, items } => {
+                self.search.set_suggestions(&keywords, items)
+            }
+            TaskOutput::Search {
+                kind,
+                page,
+                items,
+                total,
+            } => {
+                if kind == self.search.kind() {
+                    self.search.set_results(page, items, total);
+                }
+            }
+            TaskOutput::Ready {
+                track,
+                media,
+                source,
+            } => {
+                self.fetch_lrc(track.id);
+                self.fetch_cover(TaskKind::Cover, track.al.pic_url.clone());
+                self.playing = Some(track);
+                self.media = Some(media);
+                self.play_music(*source);
+                self.notify_trial();
+            }
+            TaskOutput::Prefetched {
+                id,
+                media,
+                source,
+                lrc,
+            } => {
+                if self.upcoming.as_ref().map(|u| u.track.id) == Some(id) {
+                    let position = source.as_ref().position();
+                    // 交叉淡入淡出时先静音暂停, 当前歌曲快结束时再开始
+                    let (fade, sink) = if self.crossfade().as_secs() == 0 {
+                        let fade = Fade::playing();
+                        self.append_source(&self.sink, *source, fade.clone());
+                        (fade, None)
+                    } else {
+                        let sink = self.new_sink();
+                        let fade = Fade::paused();
+                        self.append_source(&sink, *source, fade.clone());
+                        (fade, Some(sink))
+                    };
+                    self.prefetched = Some(Prefetched {
+                        media,
+                        position,
+                        fade,
+                        sink,
+                        lrc,
+                    });
+                }
+            }
+            TaskOutput::Seeked(source) => {
+                self.pending_seek = None;
+                self.replace_source(*source);
+            }
+            TaskOutput::Downloaded { track, path } => {
+                self.download = None;
+                self.notify(format!("《{}》已缓存到 {}", track.name, path));
+            }
+            TaskOutput::Lyric { id, rows } => {
+                if self.current_playing_track().map(|t| t.id) == Some(id) {
+                    self.lrc = rows;
+                }
+            }
+        }
+    }
+
+    fn on_task_error(&mut self, kind: TaskKind, e: ApiError) {
+        match kind {
+            // 轮询失败时等下一次 tick 重试, 纯音乐等没有歌词和获取搜索建议失败的情况不提示
+            TaskKind::QrCheck
+            | TaskKind::Lyric
+            | TaskKind::Logout
+            | TaskKind::Suggest
+            | TaskKind::Cover
+            | TaskKind::PlaylistCover => {}
+            // 预加载失败时等当前歌曲播完后按普通方式加载, 届时再提示
+            TaskKind::Prefetch => {}
+            // 加载播放列表失败时播放已经加载的歌曲, 下次切歌时重试
+            TaskKind::PlayingPage => {}
+            // 私人 FM 等待下一首时获取失败则退出, 提前获取失败时等播到最后一首再重试
+            TaskKind::Fm => {
+                if self.fm_waiting {
+                    self.stop_fm();
+                    self.notify_error(e);
+                }
+            }
+            TaskKind::QrCode => {
+                self.qr_login = None;
+                self.notify_error(e);
+            }
+            // 登录或加载歌单失败时回到登录页
+            TaskKind::Login | TaskKind::UserData => {
+                self.reset_user();
+                self.notify_error(e);
+            }
+            TaskKind::Download => {
+                self.download = None;
+                self.notify_error(e);
+            }
+            // 顺序保存失败时重新加载歌单, 丢弃本地的修改
+            TaskKind::PlaylistOrder => {
+                self.pending_order = None;
+                self.notify_error(e);
+                if self.shown_playlist.is_some() {
+                    self.reload_shown_playlist();
+                }
+            }
+            TaskKind::Like => {
+                if let Some((id, like)) = self.pending_like.take() {
+                    self.set_liked(id, !like);
+                }
+                self.notify_error(e);
+            }
+            // 自动切换到无法播放的歌曲时跳过, 歌单中的歌曲都无法播放时停止
+            TaskKind::Play if matches!(e, ApiError::TrackUnavailable(..)) => {
+                let skipped = self.auto_skipped.unwrap_or(0);
+                let can_skip = self.auto_skipped.is_some()
+                    && self.player_controller.mode != PlayMode::LoopOne
+                    && skipped < self.current_tracks.len();
+                if can_skip {
+                    self.notify(format!("{}, 已跳过", e));
+                    self.on_track_end();
+                } else {
+                    self.auto_skipped = None;
+                    self.notify_error(e);
+                }
+            }
+            _ => self.notify_error(e),
+        }
+    }
 }
 
 // 回调函数
@@ -454,3 +2452,8 @@ impl<'a> App<'a> {
         // self.callbacks.push(Box::new(func));
     }
 }
+
+/// 歌曲时长
+fn track_duration(track: &Track) -> Duration {
+    Duration::from_millis(track.dt.max(0) as u64)
+}
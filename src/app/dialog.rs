@@ -0,0 +1,58 @@
+use crate::api_type::api_type::Track;
+use crate::app::Input;
+use crate::util::StatefulList;
+
+/// 歌单管理的弹窗
+pub enum Dialog {
+    /// 输入歌单名, `id` 为空时新建歌单, 否则重命名
+    Name { id: Option<i64>, input: Input },
+    /// 确认删除歌单
+    Delete { id: i64, name: String },
+    /// 选择要把歌曲加入的歌单, 列表项为歌单 id 和歌单名
+    Picker {
+        track: Track,
+        playlists: StatefulList<(i64, String)>,
+    },
+}
+
+impl Dialog {
+    pub fn create() -> Self {
+        Dialog::Name {
+            id: None,
+            input: Self::name_input("新建歌单", String::new()),
+        }
+    }
+
+    pub fn rename(id: i64, name: String) -> Self {
+        Dialog::Name {
+            id: Some(id),
+            input: Self::name_input("重命名歌单", name),
+        }
+    }
+
+    pub fn picker(track: Track, playlists: Vec<(i64, String)>) -> Self {
+        let mut playlists = StatefulList::with_items(playlists);
+        if !playlists.items.is_empty() {
+            playlists.state.select(Some(0));
+        }
+        Dialog::Picker { track, playlists }
+    }
+
+    fn name_input(title: &str, name: String) -> Input {
+        let mut input = Input::default()
+            .title(title.to_string())
+            .placeholder("输入歌单名后按 Enter 保存".to_string())
+            .val(name);
+        input.focus = true;
+        input
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Dialog::Name { id: None, .. } => "新建歌单",
+            Dialog::Name { id: Some(_), .. } => "重命名歌单",
+            Dialog::Delete { .. } => "删除歌单",
+            Dialog::Picker { .. } => "加入歌单",
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use crate::app::Input;
+use qrcode::{Color, QrCode};
+use std::time::{Duration, Instant};
+
+/// 验证码重新发送的间隔
+const CAPTCHA_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 登录方式
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum LoginMode {
+    Password, // 手机号 + 密码
+    Captcha,  // 手机号 + 短信验证码
+    Email,    // 邮箱 + 密码
+    Qr,       // 扫码登录
+}
+
+impl LoginMode {
+    pub const ALL: [LoginMode; 4] = [
+        LoginMode::Password,
+        LoginMode::Captcha,
+        LoginMode::Email,
+        LoginMode::Qr,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            LoginMode::Password => "手机号密码",
+            LoginMode::Captcha => "短信验证码",
+            LoginMode::Email => "邮箱",
+            LoginMode::Qr => "扫码登录",
+        }
+    }
+
+    /// 当前登录方式需要的输入框
+    pub fn inputs(&self) -> Vec<Input> {
+        let phone = Input::default()
+            .title("📱手机号".to_string())
+            .placeholder("请输入手机号".to_string());
+        let password = Input::default()
+            .title("🔒密码".to_string())
+            .placeholder("请输入密码".to_string())
+            .is_password(true);
+        match self {
+            LoginMode::Password => vec![phone, password],
+            LoginMode::Captcha => vec![
+                phone,
+                Input::default()
+                    .title("🔑验证码".to_string())
+                    .placeholder("请输入短信验证码".to_string()),
+            ],
+            LoginMode::Email => vec![
+                Input::default()
+                    .title("📧邮箱".to_string())
+                    .placeholder("请输入网易邮箱".to_string()),
+                password,
+            ],
+            LoginMode::Qr => vec![],
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        LoginMode::ALL.iter().position(|m| m == self).unwrap_or(0)
+    }
+
+    pub fn next(&self) -> LoginMode {
+        LoginMode::ALL[(self.index() + 1) % LoginMode::ALL.len()]
+    }
+}
+
+/// 二维码状态, 对应 `/login/qr/check` 返回的 code
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum QrState {
+    Waiting,   // 801 等待扫码
+    Scanned,   // 802 已扫码, 待确认
+    Expired,   // 800 二维码过期
+    Confirmed, // 803 授权登录成功
+}
+
+impl QrState {
+    pub fn from_code(code: i32) -> Option<QrState> {
+        match code {
+            800 => Some(QrState::Expired),
+            801 => Some(QrState::Waiting),
+            802 => Some(QrState::Scanned),
+            803 => Some(QrState::Confirmed),
+            _ => None,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            QrState::Waiting => "请使用网易云音乐APP扫码登录",
+            QrState::Scanned => "已扫码, 请在手机上确认登录",
+            QrState::Expired => "二维码已过期, 按 Ctrl+r 刷新",
+            QrState::Confirmed => "登录成功",
+        }
+    }
+}
+
+/// 短信验证码发送状态
+#[derive(Default)]
+pub struct Captcha {
+    sent_at: Option<Instant>,
+}
+
+impl Captcha {
+    pub fn sent(&mut self) {
+        self.sent_at = Some(Instant::now());
+    }
+
+    /// 距离可以重新发送的剩余秒数, 0 表示可以发送
+    pub fn countdown(&self) -> u64 {
+        self.sent_at
+            .and_then(|t| CAPTCHA_INTERVAL.checked_sub(t.elapsed()))
+            .map(|d| d.as_secs() + 1)
+            .unwrap_or(0)
+    }
+}
+
+pub struct QrLogin {
+    pub key: String,
+    pub state: QrState,
+    /// 二维码每行的模块数
+    pub width: usize,
+    /// 按行排列的模块, true 为深色
+    pub modules: Vec<bool>,
+}
+
+impl QrLogin {
+    pub fn new(key: String, url: &str) -> anyhow::Result<Self> {
+        let code = QrCode::new(url.as_bytes())?;
+        Ok(Self {
+            key,
+            state: QrState::Waiting,
+            width: code.width(),
+            modules: code.to_colors().into_iter().map(|c| c == Color::Dark).collect(),
+        })
+    }
+
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.width && self.modules[y * self.width + x]
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.state == QrState::Waiting || self.state == QrState::Scanned
+    }
+}
@@ -1,7 +1,19 @@
 mod app;
+mod dialog;
 mod input;
+mod login;
+mod play_mode;
+mod queue;
+mod recommend;
+mod search;
 pub mod image;
 pub mod ui;
 
-pub use app::{App, Focus, Route};
+pub use app::{App, Focus, PlaylistSection, Route, ToastLevel};
+pub use dialog::Dialog;
 pub use input::Input;
+pub use login::{Captcha, LoginMode, QrLogin, QrState};
+pub use play_mode::{PlayMode, ShuffleOrder, Upcoming, UpcomingFrom};
+pub use queue::Queue;
+pub use recommend::Recommend;
+pub use search::Search;
@@ -0,0 +1,135 @@
+use crate::api_type::api_type::Track;
+use rand::seq::SliceRandom;
+
+/// 播放模式
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PlayMode {
+    Sequential,       // 顺序播放, 播完列表后停止
+    LoopList,         // 列表循环
+    LoopOne,          // 单曲循环
+    Shuffle,          // 随机播放
+    StopAfterCurrent, // 播完当前歌曲后停止
+}
+
+impl PlayMode {
+    pub const ALL: [PlayMode; 5] = [
+        PlayMode::Sequential,
+        PlayMode::LoopList,
+        PlayMode::LoopOne,
+        PlayMode::Shuffle,
+        PlayMode::StopAfterCurrent,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            PlayMode::Sequential => "➡️ 顺序播放",
+            PlayMode::LoopList => "🔁 列表循环",
+            PlayMode::LoopOne => "🔂 单曲循环",
+            PlayMode::Shuffle => "🔀 随机播放",
+            PlayMode::StopAfterCurrent => "⏹️ 播完停止",
+        }
+    }
+
+    pub fn next(&self) -> PlayMode {
+        let index = PlayMode::ALL.iter().position(|m| m == self).unwrap_or(0);
+        PlayMode::ALL[(index + 1) % PlayMode::ALL.len()]
+    }
+}
+
+/// 即将播放的下一首
+#[derive(Clone)]
+pub struct Upcoming {
+    pub track: Track,
+    pub from: UpcomingFrom,
+}
+
+/// 下一首的来源
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum UpcomingFrom {
+    Queue,           // 播放队列的第一首
+    Playlist(usize), // 歌单中的序号
+    Repeat,          // 单曲循环
+}
+
+impl PartialEq for Upcoming {
+    fn eq(&self, other: &Self) -> bool {
+        self.track.id == other.track.id && self.from == other.from
+    }
+}
+
+/// 随机播放的顺序, `pos` 之前的是已经播放过的歌曲, 用于返回上一首
+#[derive(Default)]
+pub struct ShuffleOrder {
+    order: Vec<usize>,
+    pos: usize,
+}
+
+impl ShuffleOrder {
+    /// 重新打乱顺序, `current` 排在第一个
+    pub fn reset(&mut self, len: usize, current: usize) {
+        let mut rest: Vec<usize> = (0..len).filter(|i| *i != current).collect();
+        rest.shuffle(&mut rand::thread_rng());
+        self.order = Vec::with_capacity(len);
+        if current < len {
+            self.order.push(current);
+        }
+        self.order.extend(rest);
+        self.pos = 0;
+    }
+
+    /// 查看下一首但不切换, 全部播放过后重新打乱
+    pub fn peek(&mut self, len: usize, current: usize) -> Option<usize> {
+        match len {
+            0 => return None,
+            1 => return Some(current),
+            _ => {}
+        }
+        if self.order.len() != len || self.order.get(self.pos) != Some(&current) {
+            self.reset(len, current);
+        }
+        if self.pos + 1 >= self.order.len() {
+            self.reset(len, current);
+        }
+        self.order.get(self.pos + 1).cloned()
+    }
+
+    /// 下一首, 和之前 `peek` 的结果一致
+    pub fn next(&mut self, len: usize, current: usize) -> Option<usize> {
+        let next = self.peek(len, current)?;
+        self.pos += 1;
+        Some(next)
+    }
+
+    /// 上一首, 没有播放记录时返回 None
+    pub fn previous(&mut self, len: usize, current: usize) -> Option<usize> {
+        if self.order.len() != len || self.order.get(self.pos) != Some(&current) || self.pos == 0 {
+            return None;
+        }
+        self.pos -= 1;
+        self.order.get(self.pos).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShuffleOrder;
+
+    #[test]
+    fn it_plays_every_track_and_goes_back_in_history() {
+        let mut shuffle = ShuffleOrder::default();
+        let mut current = 2;
+        let mut played = vec![current];
+        for _ in 0..4 {
+            current = shuffle.next(5, current).unwrap();
+            played.push(current);
+        }
+        let mut sorted = played.clone();
+        sorted.sort();
+        assert_eq!(sorted, vec![0, 1, 2, 3, 4]);
+        for expected in played.iter().rev().skip(1) {
+            current = shuffle.previous(5, current).unwrap();
+            assert_eq!(current, *expected);
+        }
+        assert_eq!(shuffle.previous(5, current), None);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::api_type::api_type::Track;
+use crate::util::StatefulList;
+
+/// 播放队列, 下一首优先从队列中取, 队列为空时继续播放歌单
+pub struct Queue {
+    pub list: StatefulList<Track>,
+}
+
+impl Queue {
+    pub fn new() -> Self {
+        Self {
+            list: StatefulList::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.items.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.items.len()
+    }
+
+    pub fn front(&self) -> Option<&Track> {
+        self.list.items.first()
+    }
+
+    /// 添加到队列末尾
+    pub fn enqueue(&mut self, track: Track) {
+        self.list.items.push(track);
+        self.fix_selection();
+    }
+
+    /// 添加到队列开头, 作为下一首播放
+    pub fn play_next(&mut self, track: Track) {
+        self.list.items.insert(0, track);
+        if let Some(i) = self.list.state.selected() {
+            self.list.state.select(Some(i + 1));
+        }
+        self.fix_selection();
+    }
+
+    /// 取出下一首
+    pub fn pop(&mut self) -> Option<Track> {
+        if self.is_empty() {
+            return None;
+        }
+        let track = self.list.items.remove(0);
+        if let Some(i) = self.list.state.selected() {
+            self.list.state.select(Some(i.saturating_sub(1)));
+        }
+        self.fix_selection();
+        Some(track)
+    }
+
+    /// 移除选中的歌曲
+    pub fn remove_selected(&mut self) -> Option<Track> {
+        let i = self.list.state.selected().filter(|i| *i < self.len())?;
+        let track = self.list.items.remove(i);
+        self.fix_selection();
+        Some(track)
+    }
+
+    /// 把选中的歌曲向前(`up`)或向后移动一位
+    pub fn move_selected(&mut self, up: bool) {
+        let i = match self.list.state.selected().filter(|i| *i < self.len()) {
+            Some(i) => i,
+            None => return,
+        };
+        let target = if up {
+            match i.checked_sub(1) {
+                Some(target) => target,
+                None => return,
+            }
+        } else if i + 1 < self.len() {
+            i + 1
+        } else {
+            return;
+        };
+        self.list.items.swap(i, target);
+        self.list.state.select(Some(target));
+    }
+
+    pub fn clear(&mut self) {
+        self.list.items.clear();
+        self.list.state.select(None);
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.is_empty() {
+            self.list.next();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.is_empty() {
+            self.list.previous();
+        }
+    }
+
+    /// 保证选中的位置在队列范围内
+    fn fix_selection(&mut self) {
+        let selected = match (self.list.state.selected(), self.len()) {
+            (_, 0) => None,
+            (Some(i), len) => Some(i.min(len - 1)),
+            (None, _) => Some(0),
+        };
+        self.list.state.select(selected);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::api_type::api_type::{RecommendPlaylist, Track};
+use crate::util::{StatefulList, TabsState};
+
+pub const TAB_SONGS: usize = 0;
+pub const TAB_PLAYLISTS: usize = 1;
+pub const TAB_FM: usize = 2;
+
+/// 每日推荐页面的状态
+pub struct Recommend {
+    pub tabs: TabsState<'static>,
+    pub songs: StatefulList<Track>,
+    pub playlists: StatefulList<RecommendPlaylist>,
+    /// 已经加载过推荐内容, 再次打开页面时不重复加载
+    pub loaded: bool,
+}
+
+impl Recommend {
+    pub fn new() -> Self {
+        Self {
+            tabs: TabsState::new(vec!["每日推荐", "推荐歌单", "私人 FM"]),
+            songs: StatefulList::new(),
+            playlists: StatefulList::new(),
+            loaded: false,
+        }
+    }
+
+    pub fn set_results(&mut self, songs: Vec<Track>, playlists: Vec<RecommendPlaylist>) {
+        self.songs = StatefulList::with_items(songs);
+        if !self.songs.items.is_empty() {
+            self.songs.state.select(Some(0));
+        }
+        self.playlists = StatefulList::with_items(playlists);
+        if !self.playlists.items.is_empty() {
+            self.playlists.state.select(Some(0));
+        }
+        self.loaded = true;
+    }
+
+    pub fn selected_song(&self) -> Option<usize> {
+        self.songs
+            .state
+            .selected()
+            .filter(|i| *i < self.songs.items.len())
+    }
+
+    pub fn selected_playlist(&self) -> Option<&RecommendPlaylist> {
+        self.playlists.items.get(self.playlists.state.selected()?)
+    }
+
+    pub fn select_next(&mut self) {
+        match self.tabs.index {
+            TAB_SONGS if !self.songs.items.is_empty() => self.songs.next(),
+            TAB_PLAYLISTS if !self.playlists.items.is_empty() => self.playlists.next(),
+            _ => {}
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        match self.tabs.index {
+            TAB_SONGS if !self.songs.items.is_empty() => self.songs.previous(),
+            TAB_PLAYLISTS if !self.playlists.items.is_empty() => self.playlists.previous(),
+            _ => {}
+        }
+    }
+}
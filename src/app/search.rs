@@ -0,0 +1,133 @@
+use crate::api_type::api_type::{SearchItem, SearchKind};
+use crate::app::Input;
+use crate::history;
+use crate::util::network::SEARCH_LIMIT;
+use crate::util::{StatefulList, TabsState};
+
+/// 搜索页面的状态
+pub struct Search {
+    pub input: Input,
+    pub tabs: TabsState<'static>,
+    pub results: StatefulList<SearchItem>,
+    /// 当前结果对应的关键词, 切换类型和翻页时使用
+    pub keywords: String,
+    /// 当前页, 从 0 开始
+    pub page: usize,
+    pub total: usize,
+    /// 搜索历史, 最近的在前面
+    pub history: Vec<String>,
+    /// 输入时显示的候选项, 输入框为空时为搜索历史, 否则为搜索建议
+    pub hints: StatefulList<String>,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self {
+            input: Input::default()
+                .title("搜索".to_string())
+                .placeholder("输入关键词后按 Enter 搜索".to_string()),
+            tabs: TabsState::new(SearchKind::ALL.iter().map(|k| k.title()).collect()),
+            results: StatefulList::new(),
+            keywords: String::new(),
+            page: 0,
+            total: 0,
+            history: history::load(),
+            hints: StatefulList::new(),
+        }
+    }
+
+    /// 输入框为空时显示搜索历史
+    pub fn showing_history(&self) -> bool {
+        self.input.val.trim().is_empty()
+    }
+
+    /// 输入变化后更新候选项, 不为空时等待搜索建议
+    pub fn reset_hints(&mut self) {
+        let items = if self.showing_history() {
+            self.history.clone()
+        } else {
+            vec![]
+        };
+        self.hints = StatefulList::with_items(items);
+    }
+
+    /// 收到搜索建议, 输入已经变化时丢弃
+    pub fn set_suggestions(&mut self, keywords: &str, items: Vec<String>) {
+        if self.input.val.trim() == keywords {
+            self.hints = StatefulList::with_items(items);
+        }
+    }
+
+    pub fn selected_hint(&self) -> Option<&String> {
+        self.hints.items.get(self.hints.state.selected()?)
+    }
+
+    pub fn select_next_hint(&mut self) {
+        if !self.hints.items.is_empty() {
+            self.hints.next();
+        }
+    }
+
+    pub fn select_previous_hint(&mut self) {
+        if !self.hints.items.is_empty() {
+            self.hints.previous();
+        }
+    }
+
+    /// 记录搜索的关键词
+    pub fn remember(&mut self, keywords: &str) {
+        history::remember(&mut self.history, keywords);
+        let _ = history::save(&self.history);
+    }
+
+    /// 删除选中的搜索历史
+    pub fn remove_selected_history(&mut self) {
+        if !self.showing_history() {
+            return;
+        }
+        let keywords = match self.selected_hint() {
+            Some(keywords) => keywords.clone(),
+            None => return,
+        };
+        self.history.retain(|k| *k != keywords);
+        let _ = history::save(&self.history);
+        let selected = self.hints.state.selected();
+        self.reset_hints();
+        let len = self.hints.items.len();
+        self.hints.state.select(selected.filter(|_| len > 0).map(|i| i.min(len - 1)));
+    }
+
+    pub fn kind(&self) -> SearchKind {
+        SearchKind::ALL[self.tabs.index]
+    }
+
+    /// 总页数, 没有结果时为 0
+    pub fn pages(&self) -> usize {
+        self.total.div_ceil(SEARCH_LIMIT)
+    }
+
+    pub fn set_results(&mut self, page: usize, items: Vec<SearchItem>, total: usize) {
+        self.page = page;
+        self.total = total;
+        self.results = StatefulList::with_items(items);
+        if !self.results.items.is_empty() {
+            self.results.state.select(Some(0));
+        }
+    }
+
+    pub fn selected(&self) -> Option<&SearchItem> {
+        self.results.items.get(self.results.state.selected()?)
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.results.items.is_empty() {
+            self.results.next();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.results.items.is_empty() {
+            self.results.previous();
+        }
+    }
+}
@@ -1,20 +1,26 @@
-use crate::app::{input::Input, App, Focus, Route};
-use crate::util::utils::{pre_format, show_duration};
+use crate::api_type::api_type::{Availability, SearchItem, Track};
+use crate::app::{input::Input, App, Dialog, Focus, LoginMode, PlaylistSection, Route, ToastLevel};
+use crate::util::network::SEARCH_LIMIT;
+use crate::util::utils::{format_bitrate, format_size, pre_format, show_duration};
 use tui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout, Rect, Margin},
+    layout::{Alignment, Constraint, Direction, Layout, Rect, Margin},
     style::{Color, Modifier, Style},
     symbols,
     text::{Span, Spans},
     widgets::canvas::{Canvas, Line, Map, MapResolution, Rectangle},
     widgets::{
-        Axis, BarChart, Block, Borders, Cell, Chart, Dataset, Gauge, LineGauge, List, ListItem,
-        Paragraph, Row, Sparkline, Table, Tabs, Wrap,
+        Axis, BarChart, Block, Borders, Cell, Chart, Clear, Dataset, Gauge, LineGauge, List, ListItem,
+        ListState, Paragraph, Row, Sparkline, Table, Tabs, Wrap,
     },
     Frame,
 };
 
 use super::image::{ColorMode, Image};
+use super::recommend::{TAB_PLAYLISTS, TAB_SONGS};
+use image::imageops::{resize, FilterType};
+use image::RgbaImage;
+use std::sync::Arc;
 
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let chunks = Layout::default()
@@ -52,6 +58,144 @@ pub fn draw_ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         Route::Home => draw_main_page(f, app),
         Route::Search => draw_search_page(f, app),
         Route::MusicAnalysis => draw_music_analysis(f, app),
+        Route::Recommend => draw_recommend_page(f, app),
+    }
+    if app.show_cache {
+        draw_cache(f, app);
+    }
+    if app.dialog.is_some() {
+        draw_dialog(f, app);
+    }
+    draw_toasts(f, app);
+}
+
+/// 屏幕中间指定大小的区域
+fn centered_rect(size: Rect, width: u16, height: u16) -> Rect {
+    let width = size.width.min(width);
+    let height = size.height.min(height);
+    Rect::new(
+        size.x + (size.width - width) / 2,
+        size.y + (size.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// 歌单管理弹窗
+fn draw_dialog<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let tick = app.system_tick;
+    let dialog = match app.dialog.as_mut() {
+        Some(dialog) => dialog,
+        None => return,
+    };
+    let title = dialog.title();
+    match dialog {
+        Dialog::Name { input, .. } => {
+            let area = centered_rect(f.size(), 50, 4);
+            f.render_widget(Clear, area);
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Length(1)].as_ref())
+                .split(area);
+            input.draw(f, chunks[0], &tick);
+            f.render_widget(
+                Paragraph::new("Enter 保存  Esc 取消").style(Style::default().bg(Color::Black)),
+                chunks[1],
+            );
+        }
+        Dialog::Delete { name, .. } => {
+            let area = centered_rect(f.size(), 50, 5);
+            let p = Paragraph::new(vec![
+                Spans::from(format!("确定删除歌单《{}》吗?", name)),
+                Spans::from(""),
+                Spans::from("Enter 删除  Esc 取消"),
+            ])
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::LightRed)),
+            );
+            f.render_widget(Clear, area);
+            f.render_widget(p, area);
+        }
+        Dialog::Picker { track, playlists } => {
+            let height = playlists.items.len() as u16 + 2;
+            let area = centered_rect(f.size(), 50, height.min(20));
+            let items: Vec<ListItem> = playlists
+                .items
+                .iter()
+                .map(|(_, name)| ListItem::new(name.as_str()))
+                .collect();
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .title(format!("把《{}》加入歌单 (Enter 确定 Esc 取消)", track.name))
+                        .borders(Borders::ALL),
+                )
+                .highlight_style(
+                    Style::default()
+                        .bg(Color::LightBlue)
+                        .add_modifier(Modifier::BOLD),
+                )
+                .highlight_symbol("👉 ");
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut playlists.state);
+        }
+    }
+}
+
+/// 缓存信息弹窗
+fn draw_cache<B: Backend>(f: &mut Frame<B>, app: &App) {
+    let stats = app.api.cache().stats();
+    let text = vec![
+        Spans::from(format!("缓存目录: {}", stats.dir.display())),
+        Spans::from(format!("已缓存: {} 首", stats.count)),
+        Spans::from(format!(
+            "占用空间: {} / {}",
+            format_size(stats.size),
+            format_size(stats.max_size)
+        )),
+        Spans::from(""),
+        Spans::from("清空缓存 Ctrl + x    关闭 Ctrl + k"),
+    ];
+    let area = centered_rect(f.size(), 60, 7);
+    let p = Paragraph::new(text)
+        .wrap(Wrap { trim: true })
+        .block(Block::default().title("缓存").borders(Borders::ALL));
+    f.render_widget(Clear, area);
+    f.render_widget(p, area);
+}
+
+/// 在右上角绘制提示信息
+fn draw_toasts<B: Backend>(f: &mut Frame<B>, app: &App) {
+    let size = f.size();
+    let width = (size.width / 3).max(30).min(size.width);
+    let mut y = size.y + 1;
+    for toast in app.toasts.iter() {
+        // 中文按两个字符宽度估算需要的行数
+        let lines = (toast.message.chars().count() * 2) as u16 / width.saturating_sub(2).max(1) + 1;
+        let height = lines.min(4) + 2;
+        if y + height > size.bottom() {
+            break;
+        }
+        let area = Rect::new(size.right() - width, y, width, height);
+        let (title, color) = match toast.level {
+            ToastLevel::Info => ("提示", Color::LightGreen),
+            ToastLevel::Error => ("错误", Color::LightRed),
+        };
+        let p = Paragraph::new(toast.message.as_str())
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(color)),
+            );
+        f.render_widget(Clear, area);
+        f.render_widget(p, area);
+        y += height;
     }
 }
 
@@ -90,9 +234,17 @@ pub fn draw_header<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
         )
         .split(area);
 
-    f.render_widget(Paragraph::new(Span::from("🎵 网易云音乐")), chunks[0]);
+    let mut title = String::from("🎵 网易云音乐");
+    if let Some(desc) = app.pending_task_desc() {
+        title.push_str(&format!(" {} {}...", spinner(app.system_tick), desc));
+    }
+    f.render_widget(Paragraph::new(Span::from(title)), chunks[0]);
 
-    f.render_widget(Paragraph::new(Span::from("🔍 搜索")), chunks[1]);
+    let mut search = String::from("🔍 搜索 Ctrl+f");
+    if let Some((name, ratio)) = app.download_progress() {
+        search.push_str(&format!("  ⬇ {} {:.0}%", name, ratio * 100.0));
+    }
+    f.render_widget(Paragraph::new(Span::from(search)), chunks[1]);
 
     let text = vec![Spans::from(vec![
         Span::from("👦 "),
@@ -112,32 +264,150 @@ pub fn draw_header<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 }
 
 pub fn draw_main_content<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    // 播放队列不为空时显示在右侧
+    let constraints = if app.queue.is_empty() {
+        vec![Constraint::Percentage(30), Constraint::Percentage(70)]
+    } else {
+        vec![
+            Constraint::Percentage(25),
+            Constraint::Percentage(50),
+            Constraint::Percentage(25),
+        ]
+    };
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
+        .constraints(constraints)
         .split(area);
     draw_playlists(f, app, chunks[0]);
     draw_tracks(f, app, chunks[1]);
+    if !app.queue.is_empty() {
+        draw_queue(f, app, chunks[2]);
+    }
+}
+
+/// 播放队列
+fn draw_queue<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .queue
+        .list
+        .items
+        .iter()
+        .enumerate()
+        .map(|(i, track)| {
+            let artist = track.ar.first().map(|a| a.name.as_str()).unwrap_or("匿名");
+            ListItem::new(Spans::from(format!("{}. {} - {}", i + 1, track.name, artist)))
+                .style(Style::default().fg(Color::Black).bg(Color::White))
+        })
+        .collect();
+    let is_focus = app.focus == Focus::Queue;
+    let title = if is_focus {
+        format!("播放队列({}) d:移除 K/J:移动 c:清空", app.queue.len())
+    } else {
+        format!("播放队列({})", app.queue.len())
+    };
+    let items = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(
+            Style::default()
+                .bg(if is_focus {
+                    Color::LightBlue
+                } else {
+                    Color::White
+                })
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("👉 ");
+    f.render_stateful_widget(items, area, &mut app.queue.list.state);
+}
+
+/// 随区域大小缩放的封面, 保持正方形
+fn cover_image<'a>(cover: Arc<RgbaImage>) -> Image<'a> {
+    // 每个字符显示上下两个像素, 传入的宽度是字符数的两倍
+    Image::with_img_fn(move |width, height| {
+        let side = (width / 2).min(height).max(1) as u32;
+        Ok(resize(cover.as_ref(), side, side, FilterType::Triangle))
+    })
+    .color_mode(ColorMode::Rgb)
 }
 
 /// 音乐播放列表
 pub fn draw_playlists<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
-    let len = app.playlists_state.items.len();
-    let items: Vec<ListItem> = (0..len)
-        .into_iter()
-        .map(|i| {
+    // 选中的歌单的封面显示在列表上方
+    let area = if app.playlists_state.items.is_empty() {
+        area
+    } else {
+        let cover_height = (area.width / 2 + 2).min(area.height / 3);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(cover_height), Constraint::Min(3)].as_ref())
+            .split(area);
+        let block = Block::default().borders(Borders::ALL);
+        match app.playlist_cover() {
+            Some(cover) => f.render_widget(cover_image(cover).block(block), chunks[0]),
+            None => f.render_widget(block, chunks[0]),
+        }
+        chunks[1]
+    };
+    let sections = app.playlist_sections();
+    if sections.is_empty() {
+        f.render_widget(Block::default().borders(Borders::ALL).title("歌单"), area);
+        return;
+    }
+    // 我喜欢的音乐只占需要的高度, 其余分组平分剩下的空间
+    let rest = sections
+        .iter()
+        .filter(|(section, ..)| *section != PlaylistSection::Liked)
+        .count() as u32;
+    let constraints: Vec<Constraint> = sections
+        .iter()
+        .map(|(section, _, len)| match section {
+            PlaylistSection::Liked => Constraint::Length(*len as u16 + 2),
+            _ => Constraint::Ratio(1, rest),
+        })
+        .collect();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints)
+        .split(area);
+    let selected = app.playlists_state.state.selected();
+    for ((section, start, len), chunk) in sections.into_iter().zip(chunks) {
+        draw_playlist_section(f, app, chunk, section, start, len, selected);
+    }
+}
+
+/// 一个分组中的歌单
+fn draw_playlist_section<B: Backend>(
+    f: &mut Frame<B>,
+    app: &App,
+    area: Rect,
+    section: PlaylistSection,
+    start: usize,
+    len: usize,
+    selected: Option<usize>,
+) {
+    let items: Vec<ListItem> = app.playlists_state.items[start..start + len]
+        .iter()
+        .enumerate()
+        .map(|(i, playlist)| {
             let lines = vec![Spans::from(format!(
                 "{}. ({}首){}",
                 pre_format(i + 1, (len + 1).to_string().len(), '0'),
-                app.playlists_state.items[i].track_count,
-                app.playlists_state.items[i].name.clone()
+                playlist.track_count,
+                playlist.name.clone()
             ))];
             ListItem::new(lines).style(Style::default().fg(Color::Black).bg(Color::White))
         })
         .collect();
-    let is_focus = app.focus == Focus::Playlist;
+    let mut state = ListState::default();
+    state.select(selected.filter(|i| (start..start + len).contains(i)).map(|i| i - start));
+    let is_focus = app.focus == Focus::Playlist && state.selected().is_some();
+    let title = if is_focus {
+        format!("{}({}) n:新建 r:重命名 d:删除", section.title(), len)
+    } else {
+        format!("{}({})", section.title(), len)
+    };
     let items = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("创建的歌单"))
+        .block(Block::default().borders(Borders::ALL).title(title))
         .highlight_style(
             Style::default()
                 .bg(if is_focus {
@@ -148,7 +418,7 @@ pub fn draw_playlists<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
                 .add_modifier(Modifier::BOLD),
         )
         .highlight_symbol("👉 ");
-    f.render_stateful_widget(items, area, &mut app.playlists_state.state);
+    f.render_stateful_widget(items, area, &mut state);
 }
 
 // pub fn draw_playlist_detail<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
@@ -168,6 +438,28 @@ pub fn draw_playlists<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 //     );
 // }
 
+/// 歌曲名前的标记
+fn availability_tag(availability: Availability) -> &'static str {
+    match availability {
+        Availability::Playable => "",
+        Availability::Vip => "[VIP] ",
+        Availability::Trial => "[试听] ",
+        Availability::Unavailable => "[无版权] ",
+    }
+}
+
+fn artist_names(track: &Track) -> String {
+    if track.ar.is_empty() {
+        return "匿名".to_string();
+    }
+    track
+        .ar
+        .iter()
+        .map(|a| a.name.as_str())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
 /// 绘制播放列表的音乐列表
 pub fn draw_tracks<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     let len = app.current_playlist_track_state.items.len();
@@ -175,9 +467,13 @@ pub fn draw_tracks<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
         .into_iter()
         .map(|i| {
             let item = &app.current_playlist_track_state.items[i];
+            let availability = item.availability();
+            let tag = availability_tag(availability);
             let lines = vec![Spans::from(format!(
-                "{}. {} - {} - [{}] - <<{}>>",
+                "{}. {}{}{} - {} - [{}] - <<{}>>",
                 pre_format(i + 1, (len + 1).to_string().len(), '0'),
+                if app.is_liked(&item.id) { "🧡 " } else { "" },
+                tag,
                 item.name.clone(),
                 show_duration(item.dt),
                 if item.ar.is_empty() {
@@ -187,12 +483,28 @@ pub fn draw_tracks<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
                 },
                 item.al.name.clone()
             ))];
-            ListItem::new(lines).style(Style::default().fg(Color::Black).bg(Color::White))
+            // 无法播放的歌曲置灰
+            let fg = if availability == Availability::Unavailable {
+                Color::Gray
+            } else {
+                Color::Black
+            };
+            ListItem::new(lines).style(Style::default().fg(fg).bg(Color::White))
         })
         .collect();
     let is_focus = app.focus == Focus::Track;
+    // 歌单还没有全部加载时显示已加载的数量
+    let count = match app.track_count() {
+        (loaded, total) if loaded < total => format!("{}/{}", loaded, total),
+        (loaded, _) => loaded.to_string(),
+    };
+    let title = if is_focus {
+        format!("歌曲列表({}) Ctrl+l:喜欢 p:加入歌单 d:移除 K/J:移动", count)
+    } else {
+        format!("歌曲列表({})", count)
+    };
     let items = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("歌曲列表"))
+        .block(Block::default().borders(Borders::ALL).title(title))
         .highlight_style(
             Style::default()
                 .bg(if is_focus {
@@ -241,26 +553,42 @@ fn draw_control_bar<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
         )
         .split(area);
 
+    // 专辑封面显示在歌曲名左侧
+    let track_area = match app.playing_cover() {
+        Some(cover) => {
+            let track_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(5), Constraint::Min(1)].as_ref())
+                .split(chunks[0]);
+            f.render_widget(
+                cover_image(cover).alignment(Alignment::Left),
+                track_chunks[0],
+            );
+            track_chunks[1]
+        }
+        None => chunks[0],
+    };
     f.render_widget(
         Paragraph::new(vec![
             Spans::from(format!(
-                "🎶 {} {}",
+                "🎶 {} {} [{}]",
                 current_track_name,
-                if loved { "🧡" } else { "🤍" }
+                if loved { "🧡" } else { "🤍" },
+                app.config.quality.title()
             )),
             Spans::from(format!("🎤 {}", current_track_artist_name)),
         ]),
-        chunks[0],
+        track_area,
+    );
+    let pause_play_text = format!(
+        "播放状态: {}  {}",
+        if !is_pause { "⏸️" } else { "▶️" },
+        app.play_mode_title()
     );
-    let pause_play_text = if !is_pause {
-        "播放状态: ⏸️"
-    } else {
-        "播放状态: ▶️"
-    };
     f.render_widget(
         Paragraph::new(vec![
             Spans::from(pause_play_text),
-            Spans::from("上一首: Ctrl+←  下一首: Ctrl+→"),
+            Spans::from("上一首: Ctrl+←  下一首: Ctrl+→  模式: Ctrl+p  音质: Ctrl+b"),
         ]),
         chunks[1],
     );
@@ -296,11 +624,10 @@ fn draw_control_bar<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 
 fn draw_percent<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     let duration = app
-        .current_playing_track()
-        .as_ref()
-        .map(|x| x.dt)
+        .playing_duration()
+        .map(|d| d.as_millis() as i32)
         .unwrap_or(100000);
-    let played = app.player_controller.seek * 1000;
+    let played = app.player_controller.played().as_millis() as i32;
     let percent = (((played as f32) * 100.0) / (duration as f32)) as u16;
     let gauge_play_duration = Gauge::default()
         .gauge_style(
@@ -340,9 +667,9 @@ fn draw_login_page<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .constraints(
             [
                 Constraint::Percentage(38),
-                Constraint::Min(3),
-                Constraint::Min(3),
-                Constraint::Percentage(62),
+                Constraint::Length(3),
+                Constraint::Min(6),
+                Constraint::Length(5),
             ]
             .as_ref(),
         )
@@ -357,26 +684,95 @@ fn draw_login_page<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         Spans::from("╚══════╝╚═╝  ╚═╝╚══════╝╚══════╝    ╚═╝     ╚═╝ ╚═════╝ ╚══════╝╚═╝ ╚═════╝"),
         Spans::from("                                                            by ustchcl     "),
     ];
-
-    let login_helper = vec![
-        Spans::from("切换和激活输入框\t Ctrl + i"),
-        Spans::from("登录 \t\t\t Ctrl + Enter"),
-    ];
     f.render_widget(Paragraph::new(app_title), chunks[0]);
-    app.inputs[0].draw(f, chunks[1], &app.system_tick);
-    app.inputs[1].draw(f, chunks[2], &app.system_tick);
-    
-    let image = Image::with_img(&app.temp_img).color_mode(ColorMode::Rgb);
-    f.render_widget(image, Rect {
-        x: 10,
-        y: 20,
-        height: 10,
-        width: 30
-    });
+
+    let titles = LoginMode::ALL
+        .iter()
+        .map(|mode| Spans::from(mode.title()))
+        .collect();
+    let tabs = Tabs::new(titles)
+        .block(Block::default().borders(Borders::ALL).title("登录方式"))
+        .highlight_style(
+            Style::default()
+                .fg(Color::LightBlue)
+                .add_modifier(Modifier::BOLD),
+        )
+        .select(app.login_mode.index());
+    f.render_widget(tabs, chunks[1]);
+
+    let mut login_helper = vec![Spans::from("切换登录方式 \t\t Ctrl + t")];
+    match app.login_mode {
+        LoginMode::Password | LoginMode::Captcha | LoginMode::Email => {
+            let form = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Length(3)].as_ref())
+                .split(chunks[2]);
+            for (input, area) in app.inputs.iter().zip(form) {
+                input.draw(f, area, &app.system_tick);
+            }
+
+            let image = Image::with_img(&app.temp_img).color_mode(ColorMode::Rgb);
+            f.render_widget(image, Rect {
+                x: 10,
+                y: 20,
+                height: 10,
+                width: 30
+            });
+
+            login_helper.push(Spans::from("切换和激活输入框\t Ctrl + i"));
+            if app.login_mode == LoginMode::Captcha {
+                let countdown = app.captcha.countdown();
+                login_helper.push(Spans::from(if countdown > 0 {
+                    format!("{}秒后可重新发送验证码", countdown)
+                } else {
+                    "发送验证码 \t\t Ctrl + s".to_string()
+                }));
+            }
+            login_helper.push(Spans::from("登录 \t\t\t Ctrl + Enter"));
+        }
+        LoginMode::Qr => {
+            draw_qr_login(f, app, chunks[2]);
+            login_helper.push(Spans::from("刷新二维码 \t\t Ctrl + r"));
+        }
+    }
 
     f.render_widget(Paragraph::new(login_helper), chunks[3]);
 }
 
+/// 绘制登录二维码, 每个字符显示上下两个模块
+fn draw_qr_login<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let qr = match app.qr_login.as_ref() {
+        Some(qr) => qr,
+        None => {
+            f.render_widget(Paragraph::new("获取二维码失败, 按 Ctrl+r 重试"), area);
+            return;
+        }
+    };
+    // 二维码四周保留的空白
+    let quiet = 2;
+    let size = qr.width + quiet * 2;
+    let is_dark = |x: usize, y: usize| x >= quiet && y >= quiet && qr.is_dark(x - quiet, y - quiet);
+    let style = Style::default().fg(Color::Black).bg(Color::White);
+
+    let mut lines: Vec<Spans> = (0..size)
+        .step_by(2)
+        .map(|y| {
+            let line: String = (0..size)
+                .map(|x| match (is_dark(x, y), is_dark(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect();
+            Spans::from(Span::styled(line, style))
+        })
+        .collect();
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(qr.state.message()));
+    f.render_widget(Paragraph::new(lines).alignment(Alignment::Center), area);
+}
+
 fn draw_input<B: Backend>(
     f: &mut Frame<B>,
     focus: bool,
@@ -406,28 +802,431 @@ fn draw_input<B: Backend>(
 
 /// 绘制搜索页面
 fn draw_search_page<B: Backend>(f: &mut Frame<B>, app: &mut App) {
-    let block = Block::default().title("搜索").borders(Borders::ALL);
-    f.render_widget(block, f.size());
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+    app.search.input.draw(f, chunks[0], &app.system_tick);
+
+    let titles = app
+        .search
+        .tabs
+        .titles
+        .iter()
+        .map(|t| Spans::from(*t))
+        .collect();
+    let tabs = Tabs::new(titles)
+        .block(Block::default().borders(Borders::ALL))
+        .select(app.search.tabs.index)
+        .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+    f.render_widget(tabs, chunks[1]);
+
+    if app.search.input.focus {
+        draw_search_hints(f, app, chunks[2]);
+    } else {
+        draw_search_results(f, app, chunks[2]);
+    }
+
+    let footer = if app.search.input.focus {
+        "Enter 搜索  ↑/↓ 选择  Delete 删除搜索历史  Esc 取消输入".to_string()
+    } else {
+        format!(
+            "第 {}/{} 页 共 {} 条  / 输入  ←/→ 切换类型  [/] 翻页  Enter 播放/打开  a 加入队列  n 下一首播放  Esc 返回",
+            (app.search.page + 1).min(app.search.pages().max(1)),
+            app.search.pages().max(1),
+            app.search.total
+        )
+    };
+    f.render_widget(Paragraph::new(Span::from(footer)), chunks[3]);
+}
+
+/// 输入时的搜索历史或搜索建议
+fn draw_search_hints<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let title = if app.search.showing_history() {
+        "搜索历史"
+    } else {
+        "搜索建议"
+    };
+    let items: Vec<ListItem> = app
+        .search
+        .hints
+        .items
+        .iter()
+        .map(|k| ListItem::new(Spans::from(k.as_str())))
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(
+            Style::default()
+                .bg(Color::LightBlue)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("👉 ");
+    f.render_stateful_widget(list, area, &mut app.search.hints.state);
+}
+
+fn draw_search_results<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let offset = app.search.page * SEARCH_LIMIT;
+    let width = (offset + app.search.results.items.len()).to_string().len();
+    let items: Vec<ListItem> = app
+        .search
+        .results
+        .items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let text = match item {
+                SearchItem::Song(track) => format!(
+                    "{}{} - {} - <<{}>> - {}",
+                    availability_tag(track.availability()),
+                    track.name,
+                    artist_names(track),
+                    track.al.name,
+                    show_duration(track.dt)
+                ),
+                SearchItem::Album(album) => {
+                    format!("{} - {} ({}首)", album.name, album.artist.name, album.size)
+                }
+                SearchItem::Artist(artist) => format!(
+                    "{} (专辑 {} 张, 歌曲 {} 首)",
+                    artist.name, artist.album_size, artist.music_size
+                ),
+                SearchItem::Playlist(playlist) => format!(
+                    "{} - {} ({}首, 播放 {} 次)",
+                    playlist.name,
+                    playlist
+                        .creator
+                        .as_ref()
+                        .map(|c| c.nickname.as_str())
+                        .unwrap_or("--"),
+                    playlist.track_count,
+                    playlist.play_count
+                ),
+                SearchItem::Lyric(track, line) => {
+                    format!("{} - {}: {}", track.name, artist_names(track), line)
+                }
+            };
+            ListItem::new(Spans::from(format!(
+                "{}. {}",
+                pre_format(offset + i + 1, width, '0'),
+                text
+            )))
+        })
+        .collect();
+    let title = if app.search.keywords.is_empty() {
+        "搜索结果".to_string()
+    } else {
+        format!("\"{}\" 的搜索结果", app.search.keywords)
+    };
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(
+            Style::default()
+                .bg(Color::LightBlue)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("👉 ");
+    f.render_stateful_widget(list, area, &mut app.search.results.state);
+}
+
+/// 绘制每日推荐页面
+fn draw_recommend_page<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+    let titles = app
+        .recommend
+        .tabs
+        .titles
+        .iter()
+        .map(|t| Spans::from(*t))
+        .collect();
+    let tabs = Tabs::new(titles)
+        .block(Block::default().borders(Borders::ALL))
+        .select(app.recommend.tabs.index)
+        .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+    f.render_widget(tabs, chunks[0]);
+
+    let footer = match app.recommend.tabs.index {
+        TAB_SONGS => "←/→ 切换  Enter 播放  a 加入队列  n 下一首播放  r 刷新  Esc 返回",
+        TAB_PLAYLISTS => "←/→ 切换  Enter 打开歌单  r 刷新  Esc 返回",
+        _ => "←/→ 切换  Enter 开启私人 FM  t 不喜欢并跳过  Esc 返回",
+    };
+    match app.recommend.tabs.index {
+        TAB_SONGS => draw_recommend_songs(f, app, chunks[1]),
+        TAB_PLAYLISTS => draw_recommend_playlists(f, app, chunks[1]),
+        _ => draw_fm(f, app, chunks[1]),
+    }
+    f.render_widget(Paragraph::new(Span::from(footer)), chunks[2]);
+}
+
+fn draw_recommend_songs<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let len = app.recommend.songs.items.len();
+    let items: Vec<ListItem> = app
+        .recommend
+        .songs
+        .items
+        .iter()
+        .enumerate()
+        .map(|(i, track)| {
+            ListItem::new(Spans::from(format!(
+                "{}. {}{}{} - {} - <<{}>> - {}",
+                pre_format(i + 1, (len + 1).to_string().len(), '0'),
+                if app.is_liked(&track.id) { "🧡 " } else { "" },
+                availability_tag(track.availability()),
+                track.name,
+                artist_names(track),
+                track.al.name,
+                show_duration(track.dt)
+            )))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(format!("每日推荐({})", len)))
+        .highlight_style(
+            Style::default()
+                .bg(Color::LightBlue)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("👉 ");
+    f.render_stateful_widget(list, area, &mut app.recommend.songs.state);
+}
+
+fn draw_recommend_playlists<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let len = app.recommend.playlists.items.len();
+    let items: Vec<ListItem> = app
+        .recommend
+        .playlists
+        .items
+        .iter()
+        .enumerate()
+        .map(|(i, playlist)| {
+            ListItem::new(vec![
+                Spans::from(format!(
+                    "{}. {} ({}首, 播放 {} 次)",
+                    pre_format(i + 1, (len + 1).to_string().len(), '0'),
+                    playlist.name,
+                    playlist.track_count,
+                    playlist.playcount
+                )),
+                Spans::from(Span::styled(
+                    format!("    {}", playlist.copywriter),
+                    Style::default().fg(Color::Gray),
+                )),
+            ])
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(format!("推荐歌单({})", len)))
+        .highlight_style(
+            Style::default()
+                .bg(Color::LightBlue)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("👉 ");
+    f.render_stateful_widget(list, area, &mut app.recommend.playlists.state);
+}
+
+/// 私人 FM 的状态和接下来的歌曲
+fn draw_fm<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let mut lines = vec![];
+    if app.fm {
+        let playing = app
+            .current_playing_track()
+            .map(|t| format!("《{}》 - {}", t.name, artist_names(t)))
+            .unwrap_or_else(|| "--".to_string());
+        lines.push(Spans::from(format!("正在播放: {}", playing)));
+        lines.push(Spans::from(""));
+        lines.push(Spans::from("接下来:"));
+        for track in app.fm_upcoming() {
+            lines.push(Spans::from(format!("  {} - {}", track.name, artist_names(track))));
+        }
+    } else {
+        lines.push(Spans::from("私人 FM 会根据你的喜好不断推荐歌曲, 按 Enter 开启"));
+    }
+    let p = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title("私人 FM"));
+    f.render_widget(p, area);
 }
 
 /// 绘制音乐播放详情
 fn draw_music_analysis<B: Backend>(f: &mut Frame<B>, app: &mut App) {
-    let block = Block::default().title("音乐").borders(Borders::ALL);
-    f.render_widget(block, f.size());
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Min(10),
+                Constraint::Length(3),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+    let body = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+        .split(chunks[0]);
+    let right = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(9), Constraint::Min(3)].as_ref())
+        .split(body[1]);
+
+    draw_cover(f, app, body[0]);
+    draw_track_info(f, app, right[0]);
+    draw_scroll_lyric(f, app, right[1]);
+
+    let duration = app
+        .playing_duration()
+        .map(|d| d.as_millis() as i32)
+        .unwrap_or(100000);
+    let played = app.player_controller.played().as_millis() as i32;
+    let percent = (((played as f32) * 100.0) / (duration as f32)) as u16;
+    let gauge = Gauge::default()
+        .block(Block::default().borders(Borders::ALL))
+        .gauge_style(Style::default().fg(Color::Yellow).bg(Color::Black))
+        .label(format!(
+            "{} {}/{}",
+            if app.player_controller.is_pause { "▶️" } else { "⏸️" },
+            show_duration(played),
+            show_duration(duration)
+        ))
+        .percent(percent.min(100));
+    f.render_widget(gauge, chunks[1]);
+    let hint = if app.fm {
+        "返回: Esc/Ctrl+n  暂停: Space  快进/快退: , . < >  跳转: 0-9  不喜欢: t"
+    } else {
+        "返回: Esc/Ctrl+n  暂停: Space  快进/快退: , . < >  跳转: 0-9"
+    };
+    f.render_widget(
+        Paragraph::new(Spans::from(hint)).alignment(Alignment::Center),
+        chunks[2],
+    );
 }
 
-/// 绘制加载页面
-fn draw_loading_page<B: Backend>(f: &mut Frame<B>, app: &mut App) {
-    let system_tick = &app.system_tick;
-    let mut text = String::from("加载中...");
-    let c = match system_tick % 4 {
+/// 绘制专辑封面, 封面未加载时显示默认图片
+fn draw_cover<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let block = Block::default().title("封面").borders(Borders::ALL);
+    let image = match app.playing_cover() {
+        Some(cover) => cover_image(cover),
+        None => Image::with_img(&app.temp_img).color_mode(ColorMode::Rgb),
+    };
+    f.render_widget(image.block(block), area);
+}
+
+/// 绘制歌曲信息
+fn draw_track_info<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let track = app.current_playing_track();
+    let field = |name: &'static str, value: String| {
+        Spans::from(vec![
+            Span::styled(name, Style::default().fg(Color::Gray)),
+            Span::raw(value),
+        ])
+    };
+    let media = app.media.as_ref();
+    let lines = vec![
+        field("歌曲: ", track.map(|t| t.name.clone()).unwrap_or_else(|| "--".to_string())),
+        field("歌手: ", track.map(artist_names).unwrap_or_else(|| "--".to_string())),
+        field("专辑: ", track.map(|t| t.al.name.clone()).unwrap_or_else(|| "--".to_string())),
+        field(
+            "时长: ",
+            track.map(|t| show_duration(t.dt)).unwrap_or_else(|| "--".to_string()),
+        ),
+        field(
+            "码率: ",
+            media
+                .and_then(|m| m.br())
+                .map(format_bitrate)
+                .unwrap_or_else(|| "--".to_string()),
+        ),
+        field(
+            "大小: ",
+            media.map(|m| format_size(m.size())).unwrap_or_else(|| "--".to_string()),
+        ),
+        field(
+            "音质: ",
+            format!(
+                "{}  模式: {}",
+                app.config.quality.title(),
+                app.play_mode_title()
+            ),
+        ),
+    ];
+    let p = Paragraph::new(lines)
+        .block(Block::default().title("正在播放").borders(Borders::ALL))
+        .wrap(Wrap { trim: true });
+    f.render_widget(p, area);
+}
+
+/// 绘制滚动歌词, 当前行高亮并保持在中间
+fn draw_scroll_lyric<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let block = Block::default().title("歌词").borders(Borders::ALL);
+    let current = match app.current_lrc_index() {
+        Some(index) => index,
+        None => {
+            let p = Paragraph::new(Spans::from("暂无歌词"))
+                .block(block)
+                .alignment(Alignment::Center);
+            f.render_widget(p, area);
+            return;
+        }
+    };
+    let lines = app
+        .lrc
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let style = if i == current {
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Gray)
+            };
+            Spans::from(Span::styled(row.content.as_str(), style))
+        })
+        .collect::<Vec<_>>();
+    let height = area.height.saturating_sub(2) as usize;
+    let offset = current.saturating_sub(height / 2) as u16;
+    let p = Paragraph::new(lines)
+        .block(block)
+        .alignment(Alignment::Center)
+        .scroll((offset, 0));
+    f.render_widget(p, area);
+}
+
+/// 加载动画
+fn spinner(tick: u64) -> char {
+    match tick % 4 {
         0 => '|',
         1 => '/',
         2 => '-',
-        3 => '\\',
-        _ => '|',
-    };
-    text.push(c);
+        _ => '\\',
+    }
+}
+
+/// 绘制加载页面
+fn draw_loading_page<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let text = format!(
+        "{}...{}  (Esc 取消)",
+        app.pending_task_desc().unwrap_or("加载中"),
+        spinner(app.system_tick)
+    );
     let p = Paragraph::new(
         Spans::from(text)
     ).block(Block::default().borders(Borders::ALL));
@@ -0,0 +1,237 @@
+use crate::api_type::api_type::Track;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const INDEX_FILE: &str = "index.json";
+/// 边听边存和手动下载的临时文件扩展名
+const PART_EXT: &str = "part";
+
+/// 缓存的一首歌曲
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CacheEntry {
+    pub id: i64,
+    /// 请求的音质对应的码率, 同一首歌不同音质分开缓存
+    pub br: i32,
+    /// 文件实际的码率, 可能低于请求的码率
+    #[serde(default)]
+    pub bitrate: i32,
+    pub name: String,
+    pub artists: String,
+    /// 缓存目录下的文件名
+    pub file: String,
+    pub size: u64,
+    /// 最后一次播放的时间, 单位秒
+    pub last_played: u64,
+}
+
+impl CacheEntry {
+    pub fn new(track: &Track, br: i32, bitrate: i32, ext: &str) -> Self {
+        Self {
+            id: track.id,
+            br,
+            bitrate,
+            name: track.name.clone(),
+            artists: track
+                .ar
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>()
+                .join("/"),
+            file: format!("{}.{}", key(track.id, br), ext),
+            size: 0,
+            last_played: now(),
+        }
+    }
+}
+
+/// 缓存统计, 用于界面显示
+pub struct CacheStats {
+    pub dir: PathBuf,
+    pub count: usize,
+    pub size: u64,
+    pub max_size: u64,
+}
+
+/// 歌曲缓存, 按歌曲 id 和音质保存, 超过大小上限时删除最久没有播放的歌曲
+pub struct Cache {
+    dir: PathBuf,
+    max_size: u64,
+    entries: HashMap<String, CacheEntry>,
+}
+
+fn key(id: i64, br: i32) -> String {
+    format!("{}-{}", id, br)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Cache {
+    /// 读取缓存目录下的索引, 丢弃文件已经不存在的记录
+    pub fn open(dir: PathBuf, max_size: u64) -> Self {
+        let mut entries: HashMap<String, CacheEntry> = fs::read_to_string(dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        entries.retain(|_, e| dir.join(&e.file).exists());
+        Self {
+            dir,
+            max_size,
+            entries,
+        }
+    }
+
+    /// 新缓存文件的保存路径
+    pub fn path(&self, entry: &CacheEntry) -> PathBuf {
+        let _ = fs::create_dir_all(&self.dir);
+        self.dir.join(&entry.file)
+    }
+
+    /// 查找已缓存的文件和文件的码率, 找到时更新播放时间, 文件已被删除时丢弃记录
+    pub fn lookup(&mut self, id: i64, br: i32) -> Option<(PathBuf, i32)> {
+        let key = key(id, br);
+        let entry = self.entries.get_mut(&key)?;
+        let path = self.dir.join(&entry.file);
+        // 旧版本的索引没有记录实际码率
+        let bitrate = if entry.bitrate > 0 { entry.bitrate } else { entry.br };
+        let exists = path.exists();
+        if exists {
+            entry.last_played = now();
+        } else {
+            self.entries.remove(&key);
+        }
+        self.save();
+        Some((path, bitrate)).filter(|_| exists)
+    }
+
+    /// 记录下载完成的文件, 超过大小上限时删除旧的缓存
+    pub fn insert(&mut self, mut entry: CacheEntry) {
+        let key = key(entry.id, entry.br);
+        entry.size = fs::metadata(self.dir.join(&entry.file))
+            .map(|m| m.len())
+            .unwrap_or(entry.size);
+        self.entries.insert(key.clone(), entry);
+        self.evict(&key);
+        self.save();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            dir: self.dir.clone(),
+            count: self.entries.len(),
+            size: self.size(),
+            max_size: self.max_size,
+        }
+    }
+
+    /// 删除所有缓存的歌曲和没有下载完成的临时文件
+    pub fn clear(&mut self) {
+        for entry in self.entries.values() {
+            let _ = fs::remove_file(self.dir.join(&entry.file));
+        }
+        if let Ok(files) = fs::read_dir(&self.dir) {
+            files
+                .filter_map(|f| f.ok())
+                .map(|f| f.path())
+                .filter(|p| p.extension().and_then(|ext| ext.to_str()) == Some(PART_EXT))
+                .for_each(|p| {
+                    let _ = fs::remove_file(p);
+                });
+        }
+        self.entries.clear();
+        self.save();
+    }
+
+    fn size(&self) -> u64 {
+        self.entries.values().map(|e| e.size).sum()
+    }
+
+    /// 按最后播放时间从旧到新删除, 直到不超过大小上限, `keep` 是刚加入的歌曲
+    fn evict(&mut self, keep: &str) {
+        let mut keys: Vec<(u64, String)> = self
+            .entries
+            .iter()
+            .filter(|(k, _)| k.as_str() != keep)
+            .map(|(k, e)| (e.last_played, k.clone()))
+            .collect();
+        keys.sort();
+        let mut size = self.size();
+        for (_, key) in keys {
+            if size <= self.max_size {
+                break;
+            }
+            if let Some(entry) = self.entries.remove(&key) {
+                let _ = fs::remove_file(self.dir.join(&entry.file));
+                size -= entry.size;
+            }
+        }
+    }
+
+    fn save(&self) {
+        if fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+        if let Ok(content) = serde_json::to_string(&self.entries) {
+            let _ = fs::write(self.dir.join(INDEX_FILE), content);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cache, CacheEntry};
+    use std::fs;
+
+    fn entry(id: i64, last_played: u64) -> CacheEntry {
+        CacheEntry {
+            id,
+            br: 128000,
+            bitrate: 128000,
+            name: id.to_string(),
+            artists: String::new(),
+            file: format!("{}.mp3", id),
+            size: 0,
+            last_played,
+        }
+    }
+
+    #[test]
+    fn it_evicts_least_recently_played() {
+        let dir = std::env::temp_dir().join(format!("ease-music-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut cache = Cache::open(dir.clone(), 25);
+        for (id, last_played) in [(1, 30), (2, 10), (3, 20)].iter() {
+            fs::write(dir.join(format!("{}.mp3", id)), [0u8; 10]).unwrap();
+            cache.insert(entry(*id, *last_played));
+        }
+        // 加入第三首时超过 25 字节, 删除最久没有播放的第二首
+        assert!(cache.lookup(2, 128000).is_none());
+        assert!(!dir.join("2.mp3").exists());
+        assert_eq!(cache.stats().count, 2);
+        assert_eq!(Cache::open(dir.clone(), 25).stats().size, 20);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn it_forgets_deleted_files() {
+        let dir = std::env::temp_dir().join(format!("ease-music-lookup-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut cache = Cache::open(dir.clone(), 100);
+        fs::write(dir.join("1.mp3"), [0u8; 10]).unwrap();
+        cache.insert(entry(1, 10));
+        fs::write(dir.join("2.mp3.stream.part"), [0u8; 10]).unwrap();
+        fs::remove_file(dir.join("1.mp3")).unwrap();
+        assert!(cache.lookup(1, 128000).is_none());
+        assert_eq!(cache.stats().count, 0);
+        cache.clear();
+        assert!(!dir.join("2.mp3.stream.part").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{Context, Result};
+use reqwest::blocking::Client;
+use reqwest::Proxy;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const APP_DIR: &str = "ease-music-terminal";
+/// 默认的配置文件名, 按顺序查找
+const CONFIG_FILES: [&str; 2] = ["config.toml", "config.json"];
+
+/// 应用配置
+///
+/// 优先级: 命令行参数 > 环境变量(支持 `.env`) > 配置文件 > 默认值
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// NeteaseCloudMusicApi 服务地址
+    pub api_url: String,
+    /// 请求超时时间, 单位秒
+    pub timeout: u64,
+    /// 代理地址, 例如 `http://127.0.0.1:7890`
+    pub proxy: Option<String>,
+    /// 歌曲缓存目录, 默认为用户缓存目录下的 `ease-music-terminal`
+    pub cache_dir: Option<String>,
+    /// 缓存大小上限, 单位 MB
+    pub cache_size: u64,
+    /// 相邻两首歌曲交叉淡入淡出的时长, 单位秒, 0 表示无缝衔接
+    pub crossfade: u64,
+    /// 播放和下载的音质
+    pub quality: Quality,
+}
+
+/// 音质, 对应接口的 `br` 参数
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    Standard,   // 标准 128k
+    Higher,     // 较高 192k
+    Exhaustive, // 极高 320k
+    Lossless,   // 无损
+}
+
+impl Quality {
+    pub const ALL: [Quality; 4] = [
+        Quality::Standard,
+        Quality::Higher,
+        Quality::Exhaustive,
+        Quality::Lossless,
+    ];
+
+    /// 请求的码率, 歌曲没有该音质时接口返回低一级的音质
+    pub fn br(&self) -> i32 {
+        match self {
+            Quality::Standard => 128000,
+            Quality::Higher => 192000,
+            Quality::Exhaustive => 320000,
+            Quality::Lossless => 999000,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Quality::Standard => "标准",
+            Quality::Higher => "较高",
+            Quality::Exhaustive => "极高",
+            Quality::Lossless => "无损",
+        }
+    }
+
+    pub fn next(&self) -> Quality {
+        let index = Quality::ALL.iter().position(|q| q == self).unwrap_or(0);
+        Quality::ALL[(index + 1) % Quality::ALL.len()]
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            api_url: "http://49.234.74.97:3000".to_string(),
+            timeout: 30,
+            proxy: None,
+            cache_dir: None,
+            cache_size: 1024,
+            crossfade: 0,
+            quality: Quality::Exhaustive,
+        }
+    }
+}
+
+impl Config {
+    /// 读取配置文件和环境变量, 未指定路径时使用用户配置目录下的 `config.toml` 或 `config.json`
+    pub fn load(path: Option<PathBuf>) -> Result<Self> {
+        let mut config = match path.or_else(Self::default_path) {
+            Some(path) if path.exists() => Self::from_file(&path)?,
+            _ => Self::default(),
+        };
+        config.merge_env();
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("无法读取配置文件 {}", path.display()))?;
+        let json = path.extension().and_then(|ext| ext.to_str()) == Some("json");
+        Self::parse(&content, json).with_context(|| format!("配置文件格式错误 {}", path.display()))
+    }
+
+    /// 按 TOML 或 JSON 格式解析配置
+    fn parse(content: &str, json: bool) -> Result<Self> {
+        Ok(if json {
+            serde_json::from_str(content)?
+        } else {
+            toml::from_str(content)?
+        })
+    }
+
+    fn merge_env(&mut self) {
+        if let Ok(api_url) = env::var("EASE_MUSIC_API_URL") {
+            self.api_url = api_url;
+        }
+        if let Some(timeout) = env::var("EASE_MUSIC_TIMEOUT")
+            .ok()
+            .and_then(|t| t.parse().ok())
+        {
+            self.timeout = timeout;
+        }
+        if let Ok(proxy) = env::var("EASE_MUSIC_PROXY") {
+            self.proxy = Some(proxy);
+        }
+    }
+
+    /// 应用数据目录, 如 `~/.config/ease-music-terminal`
+    pub fn app_dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|p| p.join(APP_DIR))
+    }
+
+    /// 默认的配置文件, 都不存在时为 `config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = Self::app_dir()?;
+        CONFIG_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.exists())
+            .or_else(|| Some(dir.join(CONFIG_FILES[0])))
+    }
+
+    /// 歌曲缓存目录, 如 `~/.cache/ease-music-terminal`
+    pub fn cache_dir(&self) -> PathBuf {
+        match self.cache_dir.as_ref().filter(|d| !d.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::cache_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join(APP_DIR),
+        }
+    }
+
+    /// 拼接 API 地址
+    pub fn api(&self, path: &str) -> String {
+        format!("{}{}", self.api_url.trim_end_matches('/'), path)
+    }
+
+    /// 根据配置创建 http client
+    pub fn build_client(&self) -> Result<Client> {
+        let mut builder = Client::builder()
+            .cookie_store(true)
+            .timeout(Duration::from_secs(self.timeout));
+        if let Some(proxy) = self.proxy.as_ref().filter(|p| !p.is_empty()) {
+            builder = builder.proxy(Proxy::all(proxy.as_str())?);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn it_parses_toml_and_json() {
+        let toml = "api_url = \"http://localhost:3000\"\ncrossfade = 3";
+        let json = r#"{"api_url": "http://localhost:3000", "crossfade": 3}"#;
+        let toml = Config::parse(toml, false).unwrap();
+        let json = Config::parse(json, true).unwrap();
+        for config in [toml, json].iter() {
+            assert_eq!(config.api_url, "http://localhost:3000");
+            assert_eq!(config.crossfade, 3);
+            assert_eq!(config.timeout, 30);
+        }
+        assert!(Config::parse("api_url = 1", true).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use crate::config::Config;
+use anyhow::Result;
+use image::RgbaImage;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 歌曲缓存目录下保存封面缩略图的子目录
+const COVER_DIR: &str = "covers";
+/// 最多保存的缩略图数, 超过时删除最旧的
+const MAX_COVERS: usize = 500;
+
+/// 缩略图的文件名, 取图片地址的最后一段, 不同尺寸分开保存
+fn file_name(url: &str, size: u32) -> String {
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .unwrap_or("")
+        .rsplit('/')
+        .next()
+        .unwrap_or("");
+    let stem = name.split('.').next().unwrap_or("");
+    let stem: String = stem
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    format!("{}-{}.png", stem, size)
+}
+
+fn dir(config: &Config) -> PathBuf {
+    config.cache_dir().join(COVER_DIR)
+}
+
+fn path(config: &Config, url: &str, size: u32) -> PathBuf {
+    dir(config).join(file_name(url, size))
+}
+
+/// 读取缓存的缩略图
+pub fn load(config: &Config, url: &str, size: u32) -> Option<RgbaImage> {
+    image::open(path(config, url, size))
+        .ok()
+        .map(|img| img.to_rgba8())
+}
+
+pub fn save(config: &Config, url: &str, size: u32, img: &RgbaImage) -> Result<()> {
+    let path = path(config, url, size);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    img.save(path)?;
+    evict(&dir(config), MAX_COVERS);
+    Ok(())
+}
+
+/// 删除所有缩略图
+pub fn clear(config: &Config) {
+    let _ = fs::remove_dir_all(dir(config));
+}
+
+/// 按修改时间从旧到新删除, 直到不超过 `max` 个
+fn evict(dir: &Path, max: usize) {
+    let mut files: Vec<(SystemTime, PathBuf)> = match fs::read_dir(dir) {
+        Ok(files) => files
+            .filter_map(|f| f.ok())
+            .filter_map(|f| Some((f.metadata().ok()?.modified().ok()?, f.path())))
+            .collect(),
+        Err(_) => return,
+    };
+    if files.len() <= max {
+        return;
+    }
+    files.sort();
+    for (_, path) in &files[..files.len() - max] {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evict, file_name};
+    use std::fs;
+
+    #[test]
+    fn it_names_covers_by_url_and_size() {
+        assert_eq!(
+            file_name("http://p1.music.126.net/abc_DEF==/109951163.jpg", 300),
+            "109951163-300.png"
+        );
+        assert_eq!(
+            file_name("https://p2.music.126.net/x/1.png?param=10y10", 60),
+            "1-60.png"
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_newest_covers() {
+        let dir = std::env::temp_dir().join(format!("ease-music-covers-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..3 {
+            fs::write(dir.join(format!("{}.png", i)), [0u8]).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        evict(&dir, 2);
+        assert!(!dir.join("0.png").exists());
+        assert!(dir.join("1.png").exists() && dir.join("2.png").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,9 +1,23 @@
 use error_chain::*;
+use reqwest::blocking::Client;
 use reqwest::header::{HeaderValue, CONTENT_LENGTH, RANGE};
 use reqwest::StatusCode;
-use std::fs::File;
-use std::io::copy;
+use std::fs::{self, File, OpenOptions};
+use crate::worker::CancelToken;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// 每个 Range 请求的大小
+const CHUNK_SIZE: u32 = 512 * 1024;
+/// 单个分块的最大重试次数
+const MAX_RETRIES: u32 = 3;
+/// 第一次重试前的等待时间
+const RETRY_DELAY: Duration = Duration::from_millis(500);
 
 error_chain! {
     foreign_links {
@@ -13,7 +27,7 @@ error_chain! {
     }
 }
 
-struct PartialRangeIter {
+pub struct PartialRangeIter {
     start: u64,
     end: u64,
     buffer_size: u32,
@@ -48,16 +62,222 @@ impl Iterator for PartialRangeIter {
     }
 }
 
-pub struct Downloader {}
+/// 下载进度, 由下载线程更新, 界面读取后显示
+#[derive(Default)]
+pub struct Progress {
+    downloaded: AtomicU64,
+    total: AtomicU64,
+}
+
+impl Progress {
+    /// 已下载的比例, 0.0 ~ 1.0
+    pub fn ratio(&self) -> f64 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        (self.downloaded.load(Ordering::Relaxed) as f64 / total as f64).min(1.0)
+    }
+
+    fn set(&self, downloaded: u64, total: u64) {
+        self.downloaded.store(downloaded, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    fn add(&self, len: u64) {
+        self.downloaded.fetch_add(len, Ordering::Relaxed);
+    }
+}
+
+/// 下载完成后用于校验文件的信息, 对应 `MusicDetail` 中的 size 和 md5
+#[derive(Default)]
+pub struct Expected {
+    pub size: u64,
+    pub md5: Option<String>,
+}
+
+pub struct Downloader {
+    client: Client,
+    progress: Arc<Progress>,
+    chunk_size: u32,
+    retries: u32,
+}
 
 impl Downloader {
-    pub fn download(url: &str, filepath: std::path::PathBuf) -> Result<()> {
-        let mut output_file = match File::create(filepath) {
-            Err(why) => panic!("couldn't create {}", why),
-            Ok(file) => file,
+    pub fn new(client: Client, progress: Arc<Progress>) -> Self {
+        Self {
+            client,
+            progress,
+            chunk_size: CHUNK_SIZE,
+            retries: MAX_RETRIES,
+        }
+    }
+
+    /// 分块下载到 `<filepath>.download.part`, 校验通过后重命名为 `filepath`
+    ///
+    /// 已存在的临时文件会从上次结束的位置继续下载, 取消时保留临时文件
+    pub fn download(
+        &self,
+        url: &str,
+        filepath: &Path,
+        expected: &Expected,
+        cancel: &CancelToken,
+    ) -> Result<()> {
+        let mut part = filepath.as_os_str().to_owned();
+        part.push(".download.part");
+        let part = PathBuf::from(part);
+        let total = match expected.size {
+            0 => self.content_length(url)?,
+            size => size,
         };
-        let response = reqwest::blocking::get(url)?.bytes()?;
-        let _ = copy(&mut response.as_ref(), &mut output_file)?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&part)?;
+        let mut start = file.metadata()?.len();
+        if start > total {
+            start = 0;
+        }
+        file.set_len(start)?;
+        file.seek(SeekFrom::End(0))?;
+        self.progress.set(start, total);
+
+        if start < total {
+            for range in PartialRangeIter::new(start, total - 1, self.chunk_size)? {
+                self.fetch_range(url, range, &mut file, cancel)?;
+            }
+        }
+        file.flush()?;
+        drop(file);
+
+        if let Err(e) = verify(&part, total, expected) {
+            // 内容有误时不能续传, 下次重新下载
+            let _ = fs::remove_file(&part);
+            return Err(e);
+        }
+        fs::rename(&part, filepath)?;
         Ok(())
     }
+
+    fn content_length(&self, url: &str) -> Result<u64> {
+        let res = self.client.head(url).send()?.error_for_status()?;
+        let len = res
+            .headers()
+            .get(CONTENT_LENGTH)
+            .ok_or("response doesn't include the content length")?;
+        Ok(u64::from_str(len.to_str()?).map_err(|_| "invalid Content-Length")?)
+    }
+
+    /// 下载一个分块, 失败时把文件截断到分块开始的位置后重试
+    fn fetch_range(
+        &self,
+        url: &str,
+        range: HeaderValue,
+        file: &mut File,
+        cancel: &CancelToken,
+    ) -> Result<()> {
+        let start = file.stream_position()?;
+        let mut attempt = 0;
+        loop {
+            match self.write_range(url, range.clone(), file, cancel) {
+                Ok(()) => return Ok(()),
+                Err(e) if cancel.is_cancelled() || attempt >= self.retries => return Err(e),
+                Err(_) => {
+                    attempt += 1;
+                    file.set_len(start)?;
+                    file.seek(SeekFrom::Start(start))?;
+                    self.progress.downloaded.store(start, Ordering::Relaxed);
+                    // 每次重试的等待时间加倍
+                    thread::sleep(RETRY_DELAY * 2u32.pow(attempt - 1));
+                }
+            }
+        }
+    }
+
+    fn write_range(
+        &self,
+        url: &str,
+        range: HeaderValue,
+        file: &mut File,
+        cancel: &CancelToken,
+    ) -> Result<()> {
+        let expected = range_len(&range)?;
+        let mut response = self.client.get(url).header(RANGE, range).send()?;
+        let status = response.status();
+        if status != StatusCode::PARTIAL_CONTENT {
+            Err(format!("unexpected response status {}", status))?;
+        }
+        let mut buffer = [0u8; 64 * 1024];
+        let mut received = 0;
+        while received < expected {
+            if cancel.is_cancelled() {
+                Err("download cancelled")?;
+            }
+            let len = response.read(&mut buffer)?;
+            if len == 0 {
+                // 内容不完整时由调用方截断后重试, 否则后面的分块会写到错误的位置
+                Err(format!("short read, expected {} bytes, got {}", expected, received))?;
+            }
+            let len = (len as u64).min(expected - received);
+            file.write_all(&buffer[..len as usize])?;
+            received += len;
+            self.progress.add(len);
+        }
+        Ok(())
+    }
+}
+
+/// `bytes=start-end` 请求的字节数
+fn range_len(range: &HeaderValue) -> Result<u64> {
+    let bounds = range
+        .to_str()?
+        .strip_prefix("bytes=")
+        .and_then(|r| r.split_once('-'))
+        .and_then(|(start, end)| Some((start.parse::<u64>().ok()?, end.parse::<u64>().ok()?)))
+        .filter(|(start, end)| start <= end);
+    match bounds {
+        Some((start, end)) => Ok(end - start + 1),
+        None => Err(format!("invalid range {:?}", range).into()),
+    }
+}
+
+/// 校验文件大小和 md5
+fn verify(path: &Path, size: u64, expected: &Expected) -> Result<()> {
+    let len = fs::metadata(path)?.len();
+    if len != size {
+        Err(format!("size mismatch, expected {} bytes, got {}", size, len))?;
+    }
+    if let Some(md5) = expected.md5.as_ref() {
+        let mut file = File::open(path)?;
+        let mut context = md5::Context::new();
+        let mut buffer = [0u8; 64 * 1024];
+        loop {
+            let len = file.read(&mut buffer)?;
+            if len == 0 {
+                break;
+            }
+            context.consume(&buffer[..len]);
+        }
+        let digest = format!("{:x}", context.compute());
+        if !digest.eq_ignore_ascii_case(md5) {
+            Err(format!("md5 mismatch, expected {}, got {}", md5, digest))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{range_len, PartialRangeIter};
+
+    #[test]
+    fn it_computes_the_length_of_each_range() {
+        let lens: Vec<u64> = PartialRangeIter::new(0, 9, 4)
+            .unwrap()
+            .map(|range| range_len(&range).unwrap())
+            .collect();
+        assert_eq!(lens, vec![4, 4, 2]);
+    }
 }
@@ -0,0 +1,53 @@
+use crate::config::Config;
+use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+
+const HISTORY_FILE: &str = "search_history.json";
+/// 最多保存的搜索记录数
+const HISTORY_LIMIT: usize = 20;
+
+fn path() -> Option<PathBuf> {
+    Config::app_dir().map(|p| p.join(HISTORY_FILE))
+}
+
+/// 读取搜索历史, 最近的在前面
+pub fn load() -> Vec<String> {
+    path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(history: &[String]) -> Result<()> {
+    let path = path().ok_or_else(|| anyhow::anyhow!("找不到配置目录"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string(history)?)?;
+    Ok(())
+}
+
+/// 把关键词移到最前面, 超过上限时丢弃最旧的记录
+pub fn remember(history: &mut Vec<String>, keywords: &str) {
+    history.retain(|k| k != keywords);
+    history.insert(0, keywords.to_string());
+    history.truncate(HISTORY_LIMIT);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::remember;
+
+    #[test]
+    fn it_moves_repeated_keywords_to_front() {
+        let mut history = vec!["a".to_string(), "b".to_string()];
+        remember(&mut history, "b");
+        assert_eq!(history, vec!["b", "a"]);
+        for i in 0..30 {
+            remember(&mut history, &i.to_string());
+        }
+        assert_eq!(history.len(), 20);
+        assert_eq!(history[0], "29");
+    }
+}
@@ -5,12 +5,22 @@ mod downloader;
 use dirs;
 mod api_type;
 mod app;
+mod cache;
+mod config;
+mod cover;
+mod history;
+mod player;
+mod session;
+mod stream;
 #[allow(dead_code)]
 mod util;
+mod worker;
 use anyhow::Result;
 
-use crate::app::{ui, App};
-use crate::util::network;
+use crate::app::{ui, App, Route};
+use crate::config::Config;
+use crate::util::network::Api;
+use crate::worker::{TaskDone, Worker};
 use argh::FromArgs;
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event as CEvent, KeyCode, KeyModifiers, KeyEvent},
@@ -20,15 +30,19 @@ use crossterm::{
 use std::{
     error::Error,
     io::{stdout, Write},
+    path::PathBuf,
     sync::mpsc,
     thread,
     time::{Duration, Instant},
 };
 use tui::{backend::CrosstermBackend, Terminal};
 
-enum Event<I> {
+pub enum Event<I> {
     Input(I),
     Tick,
+    Task(Box<TaskDone>),
+    /// 正在播放的歌曲播放结束
+    SourceEnd,
 }
 
 /// Crossterm demo
@@ -40,9 +54,38 @@ struct Cli {
     /// whether unicode symbols are used to improve the overall look of the app
     #[argh(option, default = "true")]
     enhanced_graphics: bool,
+    /// path of the config file (toml or json), defaults to `<config dir>/ease-music-terminal/config.toml`
+    #[argh(option)]
+    config: Option<String>,
+    /// base url of the NeteaseCloudMusicApi server
+    #[argh(option)]
+    api_url: Option<String>,
+    /// request timeout in seconds
+    #[argh(option)]
+    timeout: Option<u64>,
+    /// proxy used for all requests, e.g. `http://127.0.0.1:7890`
+    #[argh(option)]
+    proxy: Option<String>,
 }
 fn main() -> Result<()> {
     let cli: Cli = argh::from_env();
+    dotenv::dotenv().ok();
+    let mut config = Config::load(cli.config.map(PathBuf::from))?;
+    if let Some(api_url) = cli.api_url {
+        config.api_url = api_url;
+    }
+    if let Some(timeout) = cli.timeout {
+        config.timeout = timeout;
+    }
+    if let Some(proxy) = cli.proxy {
+        config.proxy = Some(proxy);
+    }
+    let api = Api::new(config.clone())?;
+    // 在进入 raw mode 之前初始化音频输出, 出错时能正常打印错误信息
+    let (_stream, handle) =
+        OutputStream::try_default().map_err(|e| anyhow::anyhow!("无法打开音频输出设备: {}", e))?;
+    let image = image::open("src/assets/cover.png")?.to_rgba8();
+
     enable_raw_mode()?;
 
     let mut stdout = stdout();
@@ -52,6 +95,8 @@ fn main() -> Result<()> {
 
     // setup input handling
     let (tx, rx) = mpsc::channel();
+    // 后台任务的结果和输入事件使用同一个通道
+    let worker = Worker::new(tx.clone())?;
 
     let tick_rate = Duration::from_millis(cli.tick_rate);
     thread::spawn(move || {
@@ -60,27 +105,36 @@ fn main() -> Result<()> {
             let timeout = tick_rate
                 .checked_sub(last_tick.elapsed())
                 .unwrap_or_else(|| Duration::from_secs(0));
-            if event::poll(timeout).unwrap() {
-                if let CEvent::Key(key) = event::read().unwrap() {
-                    tx.send(Event::Input(key)).unwrap();
+            // 读取终端事件失败时跳过, 主线程退出后 send 失败则结束
+            if let Ok(true) = event::poll(timeout) {
+                if let Ok(CEvent::Key(key)) = event::read() {
+                    if tx.send(Event::Input(key)).is_err() {
+                        break;
+                    }
                 }
             }
             if last_tick.elapsed() >= tick_rate {
-                tx.send(Event::Tick).unwrap();
+                if tx.send(Event::Tick).is_err() {
+                    break;
+                }
                 last_tick = Instant::now();
             }
         }
     });
 
-    let (_stream, handle) = OutputStream::try_default().unwrap();
-    let image = image::open("src/assets/cover.png")?.to_rgba8();
-    let mut app = App::new("Ease Music Termianl", &handle, image);
+    let mut app = App::new("Ease Music Termianl", &handle, image, config, api, worker);
     // network::login(&mut app)?;
     // network::get_like_list(&mut app)?;
     // network::playlists(&mut app)?;
     // network::get_playlist_detail(&mut app)?;
     terminal.clear()?;
 
+    if let Some(account) = session::load() {
+        app.goto_page(Route::Loading);
+        terminal.draw(|f| ui::draw_ui(f, &mut app))?;
+        app.restore_session(account);
+    }
+
     loop {
         terminal.draw(|f| ui::draw_ui(f, &mut app))?;
         match rx.recv()? {
@@ -91,6 +145,7 @@ fn main() -> Result<()> {
                 } => {
                     app.on_ctrl_key(code);
                 },
+                _ if app.is_editing() => app.input_on_key(event.code),
                 _ => {
                     app.input_on_key(event.code);
                     match event.code {
@@ -110,6 +165,7 @@ fn main() -> Result<()> {
                         KeyCode::Right => app.on_right(),
                         KeyCode::Down => app.on_down(),
                         KeyCode::Enter => app.on_enter(),
+                        KeyCode::Esc => app.on_esc(),
                         _ => {}
                     } 
                 }
@@ -118,6 +174,8 @@ fn main() -> Result<()> {
             Event::Tick => {
                 app.on_tick();
             }
+            Event::Task(done) => app.on_task(*done),
+            Event::SourceEnd => app.on_source_end(),
         }
     }
 
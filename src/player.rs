@@ -0,0 +1,401 @@
+use crate::stream::{ReadSeek, StreamSource};
+use crate::util::error::Result;
+use rodio::{Decoder, Source};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 可以交给 `Sink` 播放的歌曲, 记录实际播放的采样数
+pub type TrackSource = Tracked<Decoder<Box<dyn ReadSeek>>>;
+
+/// 暂停、继续和切歌时淡入淡出的时长
+pub const FADE: Duration = Duration::from_millis(300);
+
+/// 歌曲的音频数据, 跳转时从这里重新创建解码器
+pub struct Media {
+    data: MediaData,
+    /// 文件格式, 如 `mp3`、`flac`
+    ext: String,
+    /// 只能试听时试听片段的时长
+    trial: Option<Duration>,
+    /// 码率
+    br: Option<i32>,
+}
+
+enum MediaData {
+    File(PathBuf),
+    Stream(StreamSource),
+}
+
+impl Media {
+    pub fn file(path: PathBuf) -> Self {
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        Self {
+            data: MediaData::File(path),
+            ext,
+            trial: None,
+            br: None,
+        }
+    }
+
+    pub fn stream(source: StreamSource, ext: &str) -> Self {
+        Self {
+            data: MediaData::Stream(source),
+            ext: ext.to_lowercase(),
+            trial: None,
+            br: None,
+        }
+    }
+
+    pub fn with_br(mut self, br: i32) -> Self {
+        self.br = Some(br).filter(|br| *br > 0);
+        self
+    }
+
+    pub fn br(&self) -> Option<i32> {
+        self.br
+    }
+
+    /// 文件大小, 边下载边播放时为完整文件的大小
+    pub fn size(&self) -> u64 {
+        match &self.data {
+            MediaData::File(path) => std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            MediaData::Stream(source) => source.len(),
+        }
+    }
+
+    pub fn with_trial(mut self, len: Duration) -> Self {
+        self.trial = Some(len);
+        self
+    }
+
+    pub fn trial(&self) -> Option<Duration> {
+        self.trial
+    }
+
+    /// 新的读取者和数据长度
+    fn reader(&self) -> Result<(Box<dyn ReadSeek>, u64)> {
+        Ok(match &self.data {
+            MediaData::File(path) => {
+                let file = File::open(path)?;
+                let len = file.metadata()?.len();
+                (Box::new(BufReader::new(file)), len)
+            }
+            MediaData::Stream(source) => (Box::new(source.reopen()?), source.len()),
+        })
+    }
+
+    /// 按扩展名选择解码器, 避免逐个格式尝试时读取过多数据
+    fn decode(&self, reader: Box<dyn ReadSeek>) -> Result<Decoder<Box<dyn ReadSeek>>> {
+        Ok(match self.ext.as_str() {
+            "mp3" => Decoder::new_mp3(reader)?,
+            "flac" => Decoder::new_flac(reader)?,
+            _ => Decoder::new(reader)?,
+        })
+    }
+
+    /// 从 `offset` 开始解码, `duration` 为歌曲总时长
+    pub fn open_at(&self, offset: Duration, duration: Duration) -> Result<TrackSource> {
+        let (reader, len) = self.reader()?;
+        if offset == Duration::from_secs(0) {
+            return Ok(Tracked::new(self.decode(reader)?, offset));
+        }
+        if self.ext == "mp3" && duration > offset {
+            // mp3 没有索引, 按时长比例换算成字节位置, 解码器会从下一个完整的帧开始
+            let pos = (len as f64 * offset.as_secs_f64() / duration.as_secs_f64()) as u64;
+            let reader: Box<dyn ReadSeek> = Box::new(Offset::new(reader, pos)?);
+            return Ok(Tracked::new(Decoder::new_mp3(reader)?, offset));
+        }
+        // 其他格式从头解码并丢弃前面的采样
+        let mut decoder = self.decode(reader)?;
+        let rate = decoder.sample_rate() as f64 * decoder.channels() as f64;
+        let skip = (offset.as_secs_f64() * rate) as u64;
+        for _ in 0..skip {
+            if decoder.next().is_none() {
+                break;
+            }
+        }
+        Ok(Tracked::new(decoder, offset))
+    }
+}
+
+/// 从 `base` 开始读取, 对解码器来说 `base` 就是文件开头
+struct Offset<R> {
+    inner: R,
+    base: u64,
+}
+
+impl<R: Seek> Offset<R> {
+    fn new(mut inner: R, base: u64) -> io::Result<Self> {
+        inner.seek(SeekFrom::Start(base))?;
+        Ok(Self { inner, base })
+    }
+}
+
+impl<R: Read> Read for Offset<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for Offset<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(p) => self.inner.seek(SeekFrom::Start(self.base + p))?,
+            pos => self.inner.seek(pos)?,
+        };
+        if pos < self.base {
+            self.inner.seek(SeekFrom::Start(self.base))?;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            ));
+        }
+        Ok(pos - self.base)
+    }
+}
+
+/// 当前播放位置, 由实际播放的采样数计算
+#[derive(Clone, Default)]
+pub struct Position {
+    start: Duration,
+    played: Arc<AtomicU64>,
+    /// 每秒的采样数(采样率 x 声道数)
+    rate: u64,
+    /// 已经播放完
+    finished: Arc<AtomicBool>,
+    /// 被取消, 如预加载的下一首已经不需要播放
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Position {
+    pub fn get(&self) -> Duration {
+        if self.rate == 0 {
+            return self.start;
+        }
+        let played = self.played.load(Ordering::Relaxed);
+        self.start + Duration::from_secs_f64(played as f64 / self.rate as f64)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    /// 取消播放, 已经加入 `Sink` 的 source 会立即结束
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// 统计已播放采样数的 `Source`
+pub struct Tracked<S> {
+    inner: S,
+    position: Position,
+    /// 播放结束时的回调
+    on_end: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl<S: Source<Item = i16>> Tracked<S> {
+    pub fn new(inner: S, start: Duration) -> Self {
+        let rate = inner.sample_rate() as u64 * inner.channels() as u64;
+        Self {
+            inner,
+            position: Position {
+                start,
+                played: Arc::new(AtomicU64::new(0)),
+                rate,
+                finished: Arc::new(AtomicBool::new(false)),
+                cancelled: Arc::new(AtomicBool::new(false)),
+            },
+            on_end: None,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position.clone()
+    }
+
+    /// 设置播放结束时的回调, 在音频线程中调用
+    pub fn on_end<F: FnOnce() + Send + 'static>(mut self, f: F) -> Self {
+        self.on_end = Some(Box::new(f));
+        self
+    }
+
+    fn finish(&mut self) {
+        self.position.finished.store(true, Ordering::Relaxed);
+        if let Some(on_end) = self.on_end.take() {
+            on_end();
+        }
+    }
+}
+
+impl<S: Source<Item = i16>> Iterator for Tracked<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.position.cancelled.load(Ordering::Relaxed) {
+            self.finish();
+            return None;
+        }
+        match self.inner.next() {
+            Some(sample) => {
+                self.position.played.fetch_add(1, Ordering::Relaxed);
+                Some(sample)
+            }
+            None => {
+                self.finish();
+                None
+            }
+        }
+    }
+}
+
+impl<S: Source<Item = i16>> Source for Tracked<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
+
+/// 音量渐变, 主线程设置目标音量, 音频线程按时间计算当前音量
+#[derive(Clone)]
+pub struct Fade {
+    state: Arc<Mutex<FadeState>>,
+}
+
+struct FadeState {
+    from: f32,
+    to: f32,
+    start: Instant,
+    duration: Duration,
+    /// 渐变到静音后暂停
+    pause: bool,
+}
+
+impl FadeState {
+    fn gain(&self) -> f32 {
+        if self.is_done() {
+            return self.to;
+        }
+        let t = self.start.elapsed().as_secs_f32() / self.duration.as_secs_f32();
+        self.from + (self.to - self.from) * t
+    }
+
+    fn is_done(&self) -> bool {
+        self.start.elapsed() >= self.duration
+    }
+}
+
+impl Fade {
+    fn new(gain: f32, pause: bool) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(FadeState {
+                from: gain,
+                to: gain,
+                start: Instant::now(),
+                duration: Duration::from_secs(0),
+                pause,
+            })),
+        }
+    }
+
+    /// 静音并暂停, 调用 `resume` 后淡入
+    pub fn paused() -> Self {
+        Self::new(0.0, true)
+    }
+
+    /// 原音量播放
+    pub fn playing() -> Self {
+        Self::new(1.0, false)
+    }
+
+    /// 在 `duration` 内从当前音量渐变到 `to`
+    fn ramp(&self, to: f32, duration: Duration, pause: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.from = state.gain();
+        state.to = to;
+        state.start = Instant::now();
+        state.duration = duration;
+        state.pause = pause;
+    }
+
+    /// 继续播放并淡入
+    pub fn resume(&self, duration: Duration) {
+        self.ramp(1.0, duration, false);
+    }
+
+    /// 淡出后暂停
+    pub fn pause(&self, duration: Duration) {
+        self.ramp(0.0, duration, true);
+    }
+
+    /// 淡出, 之后由调用者停止播放
+    pub fn fade_out(&self, duration: Duration) {
+        self.ramp(0.0, duration, false);
+    }
+
+    /// 已经淡出到静音
+    pub fn is_silent(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.to == 0.0 && state.is_done()
+    }
+
+    /// 当前音量和是否暂停
+    fn current(&self) -> (f32, bool) {
+        let state = self.state.lock().unwrap();
+        (state.gain(), state.pause && state.is_done())
+    }
+}
+
+/// 按 `fade` 调整音量, 暂停时输出静音且不再读取 `source`, 播放位置保持不变
+pub fn faded<S>(source: S, fade: Fade) -> impl Source<Item = i16> + Send
+where
+    S: Source<Item = i16> + Send,
+{
+    let (gain, paused) = fade.current();
+    source
+        .pausable(paused)
+        .amplify(gain)
+        .periodic_access(Duration::from_millis(5), move |src| {
+            let (gain, paused) = fade.current();
+            src.set_factor(gain);
+            src.inner_mut().set_paused(paused);
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fade;
+    use std::time::Duration;
+
+    #[test]
+    fn it_pauses_after_fading_out() {
+        let fade = Fade::paused();
+        assert_eq!(fade.current(), (0.0, true));
+        fade.resume(Duration::from_secs(0));
+        assert_eq!(fade.current(), (1.0, false));
+        fade.pause(Duration::from_secs(60));
+        assert!(!fade.is_silent());
+        assert!(!fade.current().1);
+        fade.pause(Duration::from_secs(0));
+        assert!(fade.is_silent());
+        assert_eq!(fade.current(), (0.0, true));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::api_type::api_type::AccountDetail;
+use crate::config::Config;
+use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+
+const SESSION_FILE: &str = "session.json";
+
+/// Set-Cookie 中的属性字段, 不需要回传给服务器
+const COOKIE_ATTRIBUTES: [&str; 7] = [
+    "max-age", "expires", "path", "domain", "httponly", "secure", "samesite",
+];
+
+fn path() -> Option<PathBuf> {
+    Config::app_dir().map(|p| p.join(SESSION_FILE))
+}
+
+/// 读取上次保存的登录信息
+pub fn load() -> Option<AccountDetail> {
+    let content = fs::read_to_string(path()?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// 保存登录信息(包含cookie), 文件仅当前用户可读写
+pub fn save(account: &AccountDetail) -> Result<()> {
+    let path = path().ok_or_else(|| anyhow::anyhow!("找不到配置目录"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    serde_json::to_writer(options.open(path)?, account)?;
+    Ok(())
+}
+
+/// 删除保存的登录信息
+pub fn clear() -> Result<()> {
+    match path() {
+        Some(path) if path.exists() => Ok(fs::remove_file(path)?),
+        _ => Ok(()),
+    }
+}
+
+/// 把接口返回的 Set-Cookie 拼接串转换成请求头 `Cookie` 的格式
+///
+/// `MUSIC_U=xx; Max-Age=1; Path=/;;__csrf=yy; Path=/` => `MUSIC_U=xx; __csrf=yy`
+pub fn cookie_header(cookie: &str) -> String {
+    let mut pairs: Vec<(&str, &str)> = vec![];
+    for part in cookie.split(';') {
+        let mut kv = part.trim().splitn(2, '=');
+        let name = kv.next().unwrap_or("").trim();
+        let value = match kv.next() {
+            Some(value) => value.trim(),
+            None => continue,
+        };
+        if name.is_empty() || COOKIE_ATTRIBUTES.contains(&name.to_lowercase().as_str()) {
+            continue;
+        }
+        if !pairs.iter().any(|(n, _)| *n == name) {
+            pairs.push((name, value));
+        }
+    }
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cookie_header;
+
+    #[test]
+    fn it_strips_cookie_attributes() {
+        let cookie = "MUSIC_R_T=123; Max-Age=2147483647; Expires=Wed, 06 Feb 2089 14:00:00 GMT; Path=/api/feedback;;MUSIC_R_T=123; Path=/weapi/clientlog;;MUSIC_U=abc; Max-Age=1296000; Path=/; HTTPOnly;;__csrf=def; Max-Age=1296010; Path=/;";
+        assert_eq!(cookie_header(cookie), "MUSIC_R_T=123; MUSIC_U=abc; __csrf=def");
+        assert_eq!(cookie_header(""), "");
+    }
+}
@@ -0,0 +1,416 @@
+use crate::downloader::PartialRangeIter;
+use crate::util::error::{ApiError, Result};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+/// 每次 Range 请求的大小
+const CHUNK_SIZE: u64 = 256 * 1024;
+/// 连续失败多少次后放弃
+const MAX_RETRIES: u32 = 3;
+
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// 已下载的区间, 按起始位置排序且互不重叠
+#[derive(Default)]
+struct RangeSet(Vec<Range<u64>>);
+
+impl RangeSet {
+    /// 插入区间, 和相邻的区间合并
+    fn insert(&mut self, range: Range<u64>) {
+        if range.start >= range.end {
+            return;
+        }
+        let mut merged = range;
+        let mut rest = Vec::with_capacity(self.0.len() + 1);
+        for r in self.0.drain(..) {
+            if r.end < merged.start || r.start > merged.end {
+                rest.push(r);
+            } else {
+                merged = merged.start.min(r.start)..merged.end.max(r.end);
+            }
+        }
+        rest.push(merged);
+        rest.sort_by_key(|r| r.start);
+        self.0 = rest;
+    }
+
+    /// `pos` 所在区间的结束位置, 未下载时返回 None
+    fn covered_end(&self, pos: u64) -> Option<u64> {
+        self.0.iter().find(|r| r.contains(&pos)).map(|r| r.end)
+    }
+
+    /// 从 `from` 开始的第一个未下载的区间, 后面都下载完时从头查找
+    fn next_gap(&self, from: u64, len: u64) -> Option<Range<u64>> {
+        let gap_from = |from: u64| {
+            let start = self.covered_end(from).unwrap_or(from);
+            if start >= len {
+                return None;
+            }
+            let end = self
+                .0
+                .iter()
+                .map(|r| r.start)
+                .find(|s| *s > start)
+                .unwrap_or(len);
+            Some(start..end)
+        };
+        gap_from(from).or_else(|| gap_from(0))
+    }
+}
+
+struct State {
+    /// 读取者打开的文件, 下载完成后为缓存文件
+    path: PathBuf,
+    ranges: RangeSet,
+    /// 播放器正在等待的位置, 下载线程优先下载这里
+    want: u64,
+    error: Option<String>,
+    /// 共用下载数据的读取者数量
+    readers: usize,
+    /// 所有读取者都关闭后不再下载
+    closed: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+    /// 下载中的临时文件
+    part: PathBuf,
+}
+
+impl Shared {
+    fn lock(&self) -> io::Result<MutexGuard<'_, State>> {
+        self.state
+            .lock()
+            .map_err(|_| io::Error::other("stream state poisoned"))
+    }
+}
+
+impl Drop for Shared {
+    /// 下载线程和读取者都结束后删除没有下载完成的临时文件
+    fn drop(&mut self) {
+        let saved = match self.state.get_mut() {
+            Ok(state) => state.path != self.part,
+            Err(_) => false,
+        };
+        if !saved {
+            let _ = fs::remove_file(&self.part);
+        }
+    }
+}
+
+/// 边下载边播放的数据源, 从临时文件读取已下载的部分, 读到还没下载的位置时等待下载线程
+pub struct StreamSource {
+    shared: Arc<Shared>,
+    file: File,
+    pos: u64,
+    len: u64,
+}
+
+/// 下载完成后的回调
+pub type OnSaved = Box<dyn FnOnce() + Send>;
+
+/// 开始下载 `url` 并返回数据源, 下载完成后保存到 `path` 并调用 `on_saved`,
+/// `on_saved` 为空时不保存, 不再播放后删除临时文件
+pub fn open(
+    client: &Client,
+    url: &str,
+    path: PathBuf,
+    on_saved: Option<OnSaved>,
+) -> Result<StreamSource> {
+    let first = client
+        .get(url)
+        .header(RANGE, format!("bytes=0-{}", CHUNK_SIZE - 1))
+        .send()?
+        .error_for_status()?;
+    // 不支持 Range 的服务器会返回 200 和完整的文件
+    let ranged = first.status() == StatusCode::PARTIAL_CONTENT;
+    let len = if ranged {
+        first
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit('/').next())
+            .and_then(|v| v.parse::<u64>().ok())
+    } else {
+        first
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+    }
+    .filter(|len| *len > 0)
+    .ok_or_else(|| ApiError::Download("无法获取文件大小".to_string()))?;
+
+    // 和手动下载使用不同的临时文件, 同时下载同一首歌时互不影响
+    let mut part = path.clone().into_os_string();
+    part.push(".stream.part");
+    let part = PathBuf::from(part);
+    let writer = File::create(&part)?;
+    let file = File::open(&part)?;
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            path: part.clone(),
+            ranges: RangeSet::default(),
+            want: 0,
+            error: None,
+            readers: 1,
+            closed: false,
+        }),
+        cond: Condvar::new(),
+        part,
+    });
+    let fetcher = Fetcher {
+        client: client.clone(),
+        url: url.to_string(),
+        shared: shared.clone(),
+        len,
+        file: Some(writer),
+        path,
+        on_saved,
+    };
+    thread::spawn(move || fetcher.run(first, ranged));
+    Ok(StreamSource {
+        shared,
+        file,
+        pos: 0,
+        len,
+    })
+}
+
+impl StreamSource {
+    /// 创建一个从头读取的新读取者, 和当前读取者共用下载的数据
+    pub fn reopen(&self) -> io::Result<StreamSource> {
+        let mut state = self.shared.lock()?;
+        let file = File::open(&state.path)?;
+        state.readers += 1;
+        Ok(StreamSource {
+            shared: self.shared.clone(),
+            file,
+            pos: 0,
+            len: self.len,
+        })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+}
+
+impl Read for StreamSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let mut state = self.shared.lock()?;
+        loop {
+            if let Some(end) = state.ranges.covered_end(self.pos) {
+                drop(state);
+                // 只读取已下载的部分, 文件中其余位置的内容无效
+                let n = (buf.len() as u64).min(end - self.pos) as usize;
+                self.file.seek(SeekFrom::Start(self.pos))?;
+                let n = self.file.read(&mut buf[..n])?;
+                self.pos += n as u64;
+                return Ok(n);
+            }
+            if let Some(e) = state.error.as_ref() {
+                return Err(io::Error::other(e.clone()));
+            }
+            state.want = self.pos;
+            self.shared.cond.notify_all();
+            state = self
+                .shared
+                .cond
+                .wait(state)
+                .map_err(|_| io::Error::other("stream state poisoned"))?;
+        }
+    }
+}
+
+impl Seek for StreamSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::End(p) => self.len as i64 + p,
+            SeekFrom::Current(p) => self.pos as i64 + p,
+        };
+        if pos < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            ));
+        }
+        self.pos = pos as u64;
+        Ok(self.pos)
+    }
+}
+
+impl Drop for StreamSource {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.lock() {
+            state.readers = state.readers.saturating_sub(1);
+            state.closed = state.readers == 0;
+        }
+        self.shared.cond.notify_all();
+    }
+}
+
+/// 后台下载线程
+struct Fetcher {
+    client: Client,
+    url: String,
+    shared: Arc<Shared>,
+    len: u64,
+    /// 下载中的临时文件, 下载结束后关闭
+    file: Option<File>,
+    path: PathBuf,
+    on_saved: Option<OnSaved>,
+}
+
+impl Fetcher {
+    fn run(mut self, first: Response, ranged: bool) {
+        let result = if ranged {
+            // 第一块不完整时和其他区间一样重试
+            let _ = self.receive(first, 0, self.len.min(CHUNK_SIZE));
+            self.fetch_rest()
+        } else {
+            self.receive(first, 0, self.len).map(|_| ())
+        };
+        self.file = None;
+        let saved = match self.shared.lock() {
+            Ok(mut state) => {
+                let complete = state.ranges.covered_end(0) == Some(self.len);
+                match result {
+                    Err(e) => state.error = Some(e.to_string()),
+                    Ok(_) if !complete && !state.closed => {
+                        state.error = Some("连接中断".to_string())
+                    }
+                    _ => {}
+                }
+                // 下载完整的文件留作缓存, 下次直接播放, 新的读取者改为打开缓存文件
+                let saved = complete
+                    && self.on_saved.is_some()
+                    && fs::rename(&self.shared.part, &self.path).is_ok();
+                if saved {
+                    state.path = self.path.clone();
+                }
+                saved
+            }
+            Err(_) => false,
+        };
+        self.shared.cond.notify_all();
+        if let Some(on_saved) = self.on_saved.take().filter(|_| saved) {
+            on_saved();
+        }
+    }
+
+    /// 按顺序下载未下载的区间, 读取位置变化后从新的位置继续
+    fn fetch_rest(&mut self) -> Result<()> {
+        let mut failures = 0;
+        loop {
+            let gap = {
+                let state = self.shared.lock()?;
+                if state.closed {
+                    return Ok(());
+                }
+                state.ranges.next_gap(state.want, self.len)
+            };
+            let gap = match gap {
+                Some(gap) => gap,
+                None => return Ok(()),
+            };
+            let ranges = PartialRangeIter::new(gap.start, gap.end - 1, CHUNK_SIZE as u32)
+                .map_err(|e| ApiError::Download(e.to_string()))?;
+            let mut start = gap.start;
+            for range in ranges {
+                let end = (start + CHUNK_SIZE).min(gap.end);
+                let result = self
+                    .client
+                    .get(self.url.as_str())
+                    .header(RANGE, range)
+                    .send()
+                    .map_err(ApiError::from)
+                    .and_then(|res| match res.status() {
+                        StatusCode::PARTIAL_CONTENT => self.receive(res, start, end),
+                        status => Err(ApiError::Download(format!("unexpected status {}", status))),
+                    });
+                match result {
+                    Ok(true) => failures = 0,
+                    // 读取位置跳到了别处
+                    Ok(false) => break,
+                    Err(e) => {
+                        failures += 1;
+                        if failures >= MAX_RETRIES {
+                            return Err(e);
+                        }
+                        break;
+                    }
+                }
+                start = end;
+            }
+        }
+    }
+
+    /// 把响应的内容从 `start` 开始写入临时文件, 读取位置跳出 `start..end` 时提前返回 false,
+    /// 响应在 `end` 之前结束时返回错误
+    fn receive(&mut self, mut res: Response, start: u64, end: u64) -> Result<bool> {
+        let mut buffer = [0u8; 64 * 1024];
+        let mut pos = start;
+        while pos < self.len {
+            let n = res.read(&mut buffer)?;
+            if n == 0 && pos < end {
+                return Err(ApiError::Download("连接中断".to_string()));
+            }
+            if n == 0 {
+                break;
+            }
+            let n = (n as u64).min(self.len - pos);
+            if let Some(file) = self.file.as_mut() {
+                file.seek(SeekFrom::Start(pos))?;
+                file.write_all(&buffer[..n as usize])?;
+            }
+            let mut state = self.shared.lock()?;
+            state.ranges.insert(pos..pos + n);
+            pos += n;
+            self.shared.cond.notify_all();
+            if state.closed {
+                return Ok(false);
+            }
+            let want = state.want;
+            if state.ranges.covered_end(want).is_none() && (want < pos || want >= end) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RangeSet;
+
+    #[test]
+    fn it_merges_ranges_and_finds_gaps() {
+        let mut ranges = RangeSet::default();
+        ranges.insert(0..10);
+        ranges.insert(20..30);
+        assert_eq!(ranges.covered_end(5), Some(10));
+        assert_eq!(ranges.covered_end(10), None);
+        assert_eq!(ranges.next_gap(0, 40), Some(10..20));
+        assert_eq!(ranges.next_gap(25, 40), Some(30..40));
+        ranges.insert(10..20);
+        ranges.insert(30..40);
+        assert_eq!(ranges.0.len(), 1);
+        assert_eq!(ranges.covered_end(0), Some(40));
+        assert_eq!(ranges.next_gap(15, 40), None);
+    }
+}
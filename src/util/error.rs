@@ -0,0 +1,80 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, ApiError>;
+
+/// 网络请求和播放过程中的错误
+#[derive(Debug)]
+pub enum ApiError {
+    /// 请求失败, 如网络不通、超时
+    Http(reqwest::Error),
+    /// 接口返回了非200的 code
+    Status(i32, String),
+    /// 返回的数据和预期的格式不一致
+    Json(serde_json::Error),
+    /// 登录已失效
+    AuthExpired,
+    /// 配置的接口地址无效
+    InvalidUrl(String),
+    /// 歌曲无法播放, 如无版权或仅限VIP, 包含歌曲名和原因
+    TrackUnavailable(String, String),
+    /// 下载歌曲失败
+    Download(String),
+    /// 读写文件失败
+    Io(std::io::Error),
+    /// 音频解码失败
+    Decode(rodio::decoder::DecoderError),
+    /// 图片解码失败
+    Image(image::ImageError),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Http(e) => write!(f, "网络请求失败: {}", e),
+            ApiError::Status(code, msg) if msg.is_empty() => write!(f, "请求失败 ({})", code),
+            ApiError::Status(code, msg) => write!(f, "{} ({})", msg, code),
+            ApiError::Json(e) => write!(f, "数据解析失败: {}", e),
+            ApiError::AuthExpired => write!(f, "登录已失效, 请重新登录"),
+            ApiError::InvalidUrl(url) => write!(f, "无效的接口地址: {}", url),
+            ApiError::TrackUnavailable(name, reason) => {
+                write!(f, "《{}》无法播放: {}", name, reason)
+            }
+            ApiError::Download(e) => write!(f, "下载失败: {}", e),
+            ApiError::Io(e) => write!(f, "文件读写失败: {}", e),
+            ApiError::Decode(e) => write!(f, "无法解码音频: {}", e),
+            ApiError::Image(e) => write!(f, "无法解码图片: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        ApiError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        ApiError::Json(e)
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(e: std::io::Error) -> Self {
+        ApiError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for ApiError {
+    fn from(e: image::ImageError) -> Self {
+        ApiError::Image(e)
+    }
+}
+
+impl From<rodio::decoder::DecoderError> for ApiError {
+    fn from(e: rodio::decoder::DecoderError) -> Self {
+        ApiError::Decode(e)
+    }
+}
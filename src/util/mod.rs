@@ -1,5 +1,6 @@
 #[cfg(feature = "termion")]
 pub mod event;
+pub mod error;
 pub mod network;
 pub mod utils;
 mod lyric_parser;
@@ -1,81 +1,686 @@
 use crate::api_type::api_type::*;
-use crate::app::App;
-use crate::downloader::Downloader;
-use anyhow::Result;
-use reqwest::{self};
-use serde_json;
-
-fn base_url() -> String {
-    "http://49.234.74.97:3000".to_string()
-}
-
-pub fn login(app: &mut App) -> Result<()> {
-    let url = format!(
-        "{}/login/cellphone?phone={}&password={}",
-        base_url(),
-        app.inputs[0].val,
-        app.inputs[1].val,
-    );
-    let res = app.client.get(&url).send()?;
-    let account = res.json::<AccountDetail>()?;
-    app.userinfo = Option::Some(account);
+use crate::cache::{Cache, CacheEntry};
+use crate::config::Config;
+use crate::cover;
+use crate::downloader::{Downloader, Expected, Progress};
+use crate::session;
+use crate::player::Media;
+use crate::stream;
+use crate::util::error::{ApiError, Result};
+use crate::worker::CancelToken;
+use image::RgbaImage;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::COOKIE;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 每页的搜索结果数量
+pub const SEARCH_LIMIT: usize = 30;
+/// 歌单中的歌曲每次加载的数量
+pub const TRACK_PAGE: usize = 100;
+/// 用户的歌单每次加载的数量
+const PLAYLIST_PAGE: usize = 50;
+
+/// 请求接口需要的上下文, 可以 clone 到后台任务中使用
+#[derive(Clone)]
+pub struct Api {
+    pub config: Config,
+    pub client: Client,
+    /// 登录后保存的 cookie, 格式同请求头 `Cookie`
+    pub cookie: String,
+    /// 歌曲缓存, 所有任务共用一份
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl Api {
+    pub fn new(config: Config) -> anyhow::Result<Self> {
+        let client = config.build_client()?;
+        let cache = Cache::open(config.cache_dir(), config.cache_size * 1024 * 1024);
+        Ok(Self {
+            config,
+            client,
+            cookie: String::new(),
+            cache: Arc::new(Mutex::new(cache)),
+        })
+    }
+
+    pub fn cache(&self) -> MutexGuard<'_, Cache> {
+        // 其他线程 panic 时缓存的数据仍然可用
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 清除登录状态, 重新创建 client 丢弃其中的 cookie
+    pub fn reset(&mut self) {
+        self.cookie.clear();
+        if let Ok(client) = self.config.build_client() {
+            self.client = client;
+        }
+    }
+}
+
+/// 发送 GET 请求, 已登录时带上保存的 cookie
+fn get(api: &Api, url: &str) -> Result<Response> {
+    let mut req = api.client.get(url);
+    if !api.cookie.is_empty() {
+        req = req.header(COOKIE, api.cookie.as_str());
+    }
+    Ok(req.send()?)
+}
+
+/// 发送 POST 请求, 参数放在表单中, 用于参数较长的接口
+fn post(api: &Api, url: &str, form: &[(&str, String)]) -> Result<Response> {
+    let mut req = api.client.post(url).form(form);
+    if !api.cookie.is_empty() {
+        req = req.header(COOKIE, api.cookie.as_str());
+    }
+    Ok(req.send()?)
+}
+
+/// 请求接口并解析成 json
+fn fetch_value(api: &Api, url: &str) -> Result<Value> {
+    parse_value(get(api, url)?)
+}
+
+/// 解析接口返回的 json, 接口出错时返回的可能不是 json
+fn parse_value(res: Response) -> Result<Value> {
+    let status = res.status();
+    let text = res.text()?;
+    serde_json::from_str(&text).map_err(|e| {
+        if status.is_success() {
+            ApiError::Json(e)
+        } else {
+            ApiError::Status(
+                status.as_u16() as i32,
+                status.canonical_reason().unwrap_or("").to_string(),
+            )
+        }
+    })
+}
+
+/// 请求接口, 检查返回的 code 后解析成对应的类型
+fn fetch<T: DeserializeOwned>(api: &Api, url: &str) -> Result<T> {
+    check_code(fetch_value(api, url)?)
+}
+
+/// 以表单提交参数请求接口, 检查返回的 code 后解析成对应的类型
+fn fetch_form<T: DeserializeOwned>(api: &Api, url: &str, form: &[(&str, String)]) -> Result<T> {
+    check_code(parse_value(post(api, url, form)?)?)
+}
+
+fn check_code<T: DeserializeOwned>(value: Value) -> Result<T> {
+    match value.get("code").and_then(|c| c.as_i64()) {
+        None | Some(200) => {}
+        Some(301) => return Err(ApiError::AuthExpired),
+        Some(code) => {
+            let msg = value
+                .get("message")
+                .or_else(|| value.get("msg"))
+                .and_then(|m| m.as_str())
+                .unwrap_or("")
+                .to_string();
+            return Err(ApiError::Status(code as i32, msg));
+        }
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// 拼接带查询参数的接口地址, 参数会被转义
+fn api_with(api: &Api, path: &str, params: &[(&str, &str)]) -> Result<String> {
+    let base = api.config.api(path);
+    Url::parse_with_params(&base, params)
+        .map(|url| url.to_string())
+        .map_err(|_| ApiError::InvalidUrl(base))
+}
+
+/// 时间戳参数, 避免接口返回缓存的结果
+fn timestamp() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+fn md5_hex(password: &str) -> String {
+    format!("{:x}", md5::compute(password.as_bytes()))
+}
+
+/// 登录接口返回账号信息, 其中包含之后请求需要的 cookie
+fn login_with(api: &Api, url: &str) -> Result<AccountDetail> {
+    fetch::<AccountDetail>(api, url)
+}
+
+/// 手机号 + 密码登录, 密码以 md5 传输
+pub fn login_cellphone(api: &Api, phone: &str, password: &str) -> Result<AccountDetail> {
+    let url = api_with(
+        api,
+        "/login/cellphone",
+        &[("phone", phone), ("md5_password", md5_hex(password).as_str())],
+    )?;
+    login_with(api, &url)
+}
+
+/// 手机号 + 短信验证码登录
+pub fn login_captcha(api: &Api, phone: &str, captcha: &str) -> Result<AccountDetail> {
+    let url = api_with(api, "/login/cellphone", &[("phone", phone), ("captcha", captcha)])?;
+    login_with(api, &url)
+}
+
+/// 网易邮箱 + 密码登录
+pub fn login_email(api: &Api, email: &str, password: &str) -> Result<AccountDetail> {
+    let url = api_with(
+        api,
+        "/login",
+        &[("email", email), ("md5_password", md5_hex(password).as_str())],
+    )?;
+    login_with(api, &url)
+}
+
+/// 发送登录用的短信验证码
+pub fn send_captcha(api: &Api, phone: &str) -> Result<()> {
+    let url = api_with(api, "/captcha/sent", &[("phone", phone)])?;
+    fetch::<CodeRep>(api, &url)?;
+    Ok(())
+}
+
+/// 刷新登录状态, 返回接口下发的新 cookie
+pub fn login_refresh(api: &Api) -> Result<Option<String>> {
+    let url = api.config.api(&format!("/login/refresh?timestamp={}", timestamp()));
+    let refresh = fetch::<LoginRefreshRep>(api, &url)?;
+    Ok(refresh.cookie.filter(|c| !c.is_empty()))
+}
+
+/// 检查登录状态, 未登录时 account 和 profile 为空
+pub fn login_status(api: &Api) -> Result<LoginStatusData> {
+    let url = api.config.api(&format!("/login/status?timestamp={}", timestamp()));
+    let status = fetch::<LoginStatusRep>(api, &url)?;
+    Ok(status.data)
+}
+
+/// 使用 cookie 查询登录状态并组装账号信息, 未登录时返回 `AuthExpired`
+pub fn login_with_cookie(api: &mut Api, cookie: String, token: String) -> Result<AccountDetail> {
+    api.cookie = session::cookie_header(&cookie);
+    match login_status(api)? {
+        LoginStatusData {
+            code,
+            account: Some(account),
+            profile: Some(profile),
+        } => Ok(AccountDetail {
+            code,
+            account,
+            profile,
+            token,
+            cookie,
+        }),
+        _ => Err(ApiError::AuthExpired),
+    }
+}
+
+/// 使用保存的登录信息恢复登录, 先刷新一次 cookie
+pub fn restore_login(api: &mut Api, account: AccountDetail) -> Result<AccountDetail> {
+    let mut cookie = account.cookie;
+    api.cookie = session::cookie_header(&cookie);
+    if let Ok(Some(refreshed)) = login_refresh(api) {
+        // 新cookie在前, 同名字段覆盖旧值
+        cookie = format!("{};;{}", refreshed, cookie);
+    }
+    login_with_cookie(api, cookie, account.token)
+}
+
+/// 获取扫码登录的 key
+pub fn qr_key(api: &Api) -> Result<String> {
+    let url = api.config.api(&format!("/login/qr/key?timestamp={}", timestamp()));
+    let rep = fetch::<QrKeyRep>(api, &url)?;
+    Ok(rep.data.unikey)
+}
+
+/// 根据 key 获取二维码的内容
+pub fn qr_create(api: &Api, key: &str) -> Result<String> {
+    let url = api.config.api(&format!(
+        "/login/qr/create?key={}&timestamp={}",
+        key,
+        timestamp()
+    ));
+    let rep = fetch::<QrCreateRep>(api, &url)?;
+    Ok(rep.data.qrurl)
+}
+
+/// 查询二维码的扫码状态
+pub fn qr_check(api: &Api, key: &str) -> Result<QrCheckRep> {
+    let url = api.config.api(&format!(
+        "/login/qr/check?key={}&timestamp={}",
+        key,
+        timestamp()
+    ));
+    // 扫码状态通过 code 返回, 不能当作错误处理
+    Ok(serde_json::from_value(fetch_value(api, &url)?)?)
+}
+
+pub fn logout(api: &Api) -> Result<()> {
+    let url = api.config.api("/logout");
+    fetch::<CodeRep>(api, &url)?;
+    Ok(())
+}
+
+/// 用户创建和收藏的全部歌单, 新建歌单后需要立即看到, 不使用缓存
+pub fn playlists(api: &Api, user_id: i64) -> Result<Vec<Playlist>> {
+    let mut playlists = vec![];
+    loop {
+        let url = api.config.api(&format!(
+            "/user/playlist?uid={}&limit={}&offset={}&timestamp={}",
+            user_id,
+            PLAYLIST_PAGE,
+            playlists.len(),
+            timestamp()
+        ));
+        let user_playlist = fetch::<UserPlaylist>(api, &url)?;
+        let more = user_playlist.more && !user_playlist.playlist.is_empty();
+        playlists.extend(user_playlist.playlist);
+        if !more {
+            return Ok(playlists);
+        }
+    }
+}
+
+/// 新建歌单, 返回歌单 id
+pub fn create_playlist(api: &Api, name: &str) -> Result<i64> {
+    let url = api_with(
+        api,
+        "/playlist/create",
+        &[("name", name), ("timestamp", &timestamp().to_string())],
+    )?;
+    Ok(fetch::<CreatePlaylistRep>(api, &url)?.id)
+}
+
+pub fn rename_playlist(api: &Api, id: i64, name: &str) -> Result<()> {
+    let url = api_with(
+        api,
+        "/playlist/name/update",
+        &[
+            ("id", &id.to_string()),
+            ("name", name),
+            ("timestamp", &timestamp().to_string()),
+        ],
+    )?;
+    fetch::<CodeRep>(api, &url)?;
     Ok(())
 }
 
-pub fn playlists(app: &mut App) -> Result<()> {
-    let user_id = app.userinfo.as_ref().map(|a| a.account.id).unwrap_or(1);
-    let url = format!("{}/user/playlist?uid={}", base_url(), user_id);
-    let res = app.client.get(&url).send()?;
-    let user_playlist = res.json::<UserPlaylist>()?;
-    app.set_playlists(user_playlist.playlist);
+pub fn delete_playlist(api: &Api, id: i64) -> Result<()> {
+    let url = api.config.api(&format!(
+        "/playlist/delete?id={}&timestamp={}",
+        id,
+        timestamp()
+    ));
+    fetch::<CodeRep>(api, &url)?;
     Ok(())
 }
 
-pub fn get_playlist_detail(app: &mut App) -> Result<()> {
-    let id = app.playlists_state.items[app.playlists_state.state.selected().unwrap_or(0)].id;
-    let url = format!("{}/playlist/detail?id={}", base_url(), id);
-    let res = app.client.get(&url).send()?;
-    let playlist_detail = res.json::<PlaylistDetailRep>()?;
-    app.set_current_playlist(playlist_detail.playlist.tracks);
+/// 向歌单添加(`add` 为 true)或从歌单删除歌曲
+pub fn update_playlist_tracks(api: &Api, id: i64, track_ids: &[i64], add: bool) -> Result<()> {
+    let url = api.config.api(&format!(
+        "/playlist/tracks?op={}&pid={}&tracks={}&timestamp={}",
+        if add { "add" } else { "del" },
+        id,
+        join_ids(track_ids),
+        timestamp()
+    ));
+    fetch::<CodeRep>(api, &url)?;
     Ok(())
 }
 
-pub fn get_like_list(app: &mut App) -> Result<()> {
-    let user_id = app.userinfo.as_ref().map(|a| a.account.id).unwrap_or(1);
-    let url = format!("{}/likelist?uid={}", base_url(), user_id);
-    let res = app.client.get(&url).send()?;
-    let like_list = res.json::<LikeListRep>()?;
-    app.likelist = like_list.ids;
+/// 按给定的顺序保存歌单中的歌曲, 歌曲较多时 id 超过地址长度限制, 放在表单中提交
+pub fn reorder_playlist(api: &Api, id: i64, track_ids: &[i64]) -> Result<()> {
+    let url = api.config.api(&format!("/song/order/update?timestamp={}", timestamp()));
+    let form = [
+        ("pid", id.to_string()),
+        ("ids", format!("[{}]", join_ids(track_ids))),
+    ];
+    fetch_form::<CodeRep>(api, &url, &form)?;
     Ok(())
 }
 
-pub fn get_music_detail(ids: Vec<i64>, app: &App) -> Result<Vec<MusicDetail>> {
-    let url = format!(
-        "{}/song/url?id={}",
-        base_url(),
+fn join_ids(ids: &[i64]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 歌单中的歌曲, 歌曲较多时剩下的歌曲需要按 id 分批加载
+pub fn get_playlist_detail(api: &Api, id: i64) -> Result<TrackPage> {
+    let url = api.config.api(&format!("/playlist/detail?id={}", id));
+    let playlist_detail = fetch::<PlaylistDetailRep>(api, &url)?;
+    let tracks = with_privileges(
+        playlist_detail.playlist.tracks,
+        playlist_detail.privileges,
+    );
+    let more = remaining_ids(&tracks, playlist_detail.playlist.track_ids);
+    Ok(TrackPage { tracks, more })
+}
+
+/// 还没有返回的歌曲 id, 保持歌单中的顺序
+fn remaining_ids(tracks: &[Track], track_ids: Vec<TrackId>) -> Vec<i64> {
+    let loaded: HashSet<i64> = tracks.iter().map(|t| t.id).collect();
+    track_ids
+        .into_iter()
+        .map(|t| t.id)
+        .filter(|id| !loaded.contains(id))
+        .collect()
+}
+
+/// 按 id 批量获取歌曲
+pub fn get_song_detail(api: &Api, ids: &[i64]) -> Result<Vec<Track>> {
+    let url = api.config.api(&format!("/song/detail?ids={}", join_ids(ids)));
+    let song_detail = fetch::<SongDetailRep>(api, &url)?;
+    Ok(with_privileges(song_detail.songs, song_detail.privileges))
+}
+
+/// 把单独返回的播放权限合并到歌曲中
+fn with_privileges(mut tracks: Vec<Track>, privileges: Vec<Privilege>) -> Vec<Track> {
+    let mut privileges: HashMap<i64, Privilege> =
+        privileges.into_iter().map(|p| (p.id, p)).collect();
+    for track in tracks.iter_mut().filter(|t| t.privilege.is_none()) {
+        track.privilege = privileges.remove(&track.id);
+    }
+    tracks
+}
+
+/// 喜欢的音乐的 id 列表
+pub fn get_like_list(api: &Api, user_id: i64) -> Result<Vec<i64>> {
+    let url = api.config.api(&format!("/likelist?uid={}", user_id));
+    let like_list = fetch::<LikeListRep>(api, &url)?;
+    Ok(like_list.ids)
+}
+
+/// 喜欢或取消喜欢歌曲
+pub fn like(api: &Api, id: i64, like: bool) -> Result<()> {
+    let url = api.config.api(&format!(
+        "/like?id={}&like={}&timestamp={}",
+        id,
+        like,
+        timestamp()
+    ));
+    fetch::<CodeRep>(api, &url)?;
+    Ok(())
+}
+
+/// 每日推荐的歌曲
+pub fn get_recommend_songs(api: &Api) -> Result<Vec<Track>> {
+    let url = api.config.api("/recommend/songs");
+    Ok(fetch::<RecommendSongsRep>(api, &url)?.data.daily_songs)
+}
+
+/// 每日推荐的歌单
+pub fn get_recommend_playlists(api: &Api) -> Result<Vec<RecommendPlaylist>> {
+    let url = api.config.api("/recommend/resource");
+    Ok(fetch::<RecommendPlaylistsRep>(api, &url)?.recommend)
+}
+
+/// 私人 FM 的下一批歌曲, 每次返回几首
+pub fn get_personal_fm(api: &Api) -> Result<Vec<Track>> {
+    let url = api.config.api(&format!("/personal_fm?timestamp={}", timestamp()));
+    let ids: Vec<i64> = fetch::<PersonalFmRep>(api, &url)?
+        .data
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+    get_song_detail(api, &ids)
+}
+
+/// 私人 FM 中不喜欢的歌曲, 以后不再推荐
+pub fn fm_trash(api: &Api, id: i64) -> Result<()> {
+    let url = api.config.api(&format!("/fm_trash?id={}&timestamp={}", id, timestamp()));
+    fetch::<CodeRep>(api, &url)?;
+    Ok(())
+}
+
+/// 按配置的音质获取播放地址
+pub fn get_music_detail(ids: Vec<i64>, api: &Api) -> Result<Vec<MusicDetail>> {
+    let url = api.config.api(&format!(
+        "/song/url?id={}&br={}",
         ids.iter()
             .map(|id| id.to_string())
             .collect::<Vec<_>>()
-            .join(",")
-    );
-    let res = app.client.get(&url).send()?;
-    let musics = res.json::<Musics>()?;
+            .join(","),
+        api.config.quality.br()
+    ));
+    let musics = fetch::<Musics>(api, &url)?;
     Ok(musics.data)
 }
 
-pub fn download_music(url: &str, music_name: &str) -> Option<String> {
-    let filepath = dirs::audio_dir().map(|p| p.join(music_name)).unwrap();
-    let str = filepath.to_str().map(|str| str.to_string());
-    if !filepath.exists() {
-        let _ = Downloader::download(url, filepath);
+/// 获取歌曲信息和播放地址, 没有地址时说明无法播放
+fn music_detail(api: &Api, track: &Track) -> Result<(MusicDetail, String)> {
+    let detail = get_music_detail(vec![track.id], api)?.into_iter().next();
+    let url = detail
+        .as_ref()
+        .and_then(|d| d.url.clone())
+        .filter(|url| !url.is_empty());
+    match (detail, url) {
+        (Some(detail), Some(url)) => Ok((detail, url)),
+        (detail, _) => Err(ApiError::TrackUnavailable(
+            track.name.clone(),
+            detail
+                .map(|d| d.unavailable_reason())
+                .unwrap_or("没有版权")
+                .to_string(),
+        )),
     }
-    str
 }
 
+/// 播放地址中的文件扩展名
+fn file_ext(music_url: &str) -> &str {
+    let path = music_url.split('?').next().unwrap_or(music_url);
+    match path.rsplit('/').next().and_then(|name| name.rsplit_once('.')) {
+        Some((_, ext)) if !ext.is_empty() => ext,
+        _ => "mp3",
+    }
+}
+
+/// 下载歌曲到缓存目录, 校验文件大小和 md5, 返回本地文件路径
+pub fn download_track(
+    api: &Api,
+    track: &Track,
+    progress: Arc<Progress>,
+    cancel: &CancelToken,
+) -> Result<String> {
+    let br = api.config.quality.br();
+    if let Some((path, _)) = api.cache().lookup(track.id, br) {
+        return Ok(path.to_string_lossy().to_string());
+    }
+    let (detail, url) = music_detail(api, track)?;
+    if detail.free_trial_info.is_some() {
+        return Err(ApiError::TrackUnavailable(
+            track.name.clone(),
+            "只能试听, 无法下载".to_string(),
+        ));
+    }
+    let entry = CacheEntry::new(track, br, detail.br, file_ext(&url));
+    let filepath = api.cache().path(&entry);
+    let expected = Expected {
+        size: detail.size.max(0) as u64,
+        md5: detail.md5.filter(|md5| !md5.is_empty()),
+    };
+    Downloader::new(api.client.clone(), progress)
+        .download(&url, &filepath, &expected, cancel)
+        .map_err(|e| ApiError::Download(e.to_string()))?;
+    api.cache().insert(entry);
+    Ok(filepath.to_string_lossy().to_string())
+}
+
+/// 打开歌曲用于播放, 已缓存当前音质的读取本地文件, 否则边下载边播放, 下载完成后加入缓存
+pub fn open_track(api: &Api, track: &Track) -> Result<Media> {
+    let br = api.config.quality.br();
+    if let Some((path, bitrate)) = api.cache().lookup(track.id, br) {
+        return Ok(Media::file(path).with_br(bitrate));
+    }
+    let (detail, url) = music_detail(api, track)?;
+    let ext = file_ext(&url);
+    // 试听片段不加入缓存, 不再播放后删除
+    if let Some(trial) = detail.free_trial_info {
+        let filepath = std::env::temp_dir().join(format!("ease-music-trial-{}.{}", track.id, ext));
+        let source = stream::open(&api.client, &url, filepath, None)?;
+        let len = Duration::from_secs_f64((trial.end - trial.start).max(0.0));
+        return Ok(Media::stream(source, ext).with_br(detail.br).with_trial(len));
+    }
+    let entry = CacheEntry::new(track, br, detail.br, ext);
+    let filepath = api.cache().path(&entry);
+    let owner = api.clone();
+    let on_saved = Box::new(move || owner.cache().insert(entry));
+    let source = stream::open(&api.client, &url, filepath, Some(on_saved))?;
+    Ok(Media::stream(source, ext).with_br(detail.br))
+}
+
+/// 搜索, `page` 从 0 开始, 返回一页结果和结果总数
+pub fn search(
+    api: &Api,
+    keywords: &str,
+    kind: SearchKind,
+    page: usize,
+) -> Result<(Vec<SearchItem>, usize)> {
+    let url = api_with(
+        api,
+        "/cloudsearch",
+        &[
+            ("keywords", keywords),
+            ("type", kind.code().to_string().as_str()),
+            ("limit", SEARCH_LIMIT.to_string().as_str()),
+            ("offset", (page * SEARCH_LIMIT).to_string().as_str()),
+        ],
+    )?;
+    let result = fetch::<SearchRep>(api, &url)?.result;
+    Ok(match kind {
+        SearchKind::Song => (
+            result.songs.into_iter().map(|s| SearchItem::Song(s.track)).collect(),
+            result.song_count,
+        ),
+        SearchKind::Album => (
+            result.albums.into_iter().map(SearchItem::Album).collect(),
+            result.album_count,
+        ),
+        SearchKind::Artist => (
+            result.artists.into_iter().map(SearchItem::Artist).collect(),
+            result.artist_count,
+        ),
+        SearchKind::Playlist => (
+            result.playlists.into_iter().map(SearchItem::Playlist).collect(),
+            result.playlist_count,
+        ),
+        SearchKind::Lyric => (
+            result
+                .songs
+                .into_iter()
+                .map(|s| {
+                    let line = s
+                        .lyrics
+                        .as_ref()
+                        .map(|l| lyric_snippet(l, keywords))
+                        .unwrap_or_default();
+                    SearchItem::Lyric(s.track, line)
+                })
+                .collect(),
+            result.song_count,
+        ),
+    })
+}
+
+/// 输入时的搜索建议
+pub fn search_suggest(api: &Api, keywords: &str) -> Result<Vec<String>> {
+    let url = api_with(
+        api,
+        "/search/suggest",
+        &[("keywords", keywords), ("type", "mobile")],
+    )?;
+    let rep = fetch::<SuggestRep>(api, &url)?;
+    Ok(rep.result.all_match.into_iter().map(|m| m.keyword).collect())
+}
+
+/// 从搜索结果的歌词中取出包含关键词的一行, 去掉高亮标签
+fn lyric_snippet(lyrics: &Value, keywords: &str) -> String {
+    let text = match lyrics {
+        Value::Array(lines) => lines
+            .iter()
+            .filter_map(|l| l.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Object(obj) => obj
+            .get("txt")
+            .and_then(|t| t.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        _ => String::new(),
+    };
+    let text = text.replace("<b>", "").replace("</b>", "");
+    let keywords = keywords.to_lowercase();
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    let first = lines.clone().next().unwrap_or_default();
+    lines
+        .find(|l| l.to_lowercase().contains(&keywords))
+        .unwrap_or(first)
+        .to_string()
+}
+
+/// 专辑中的歌曲
+pub fn get_album_tracks(api: &Api, id: i64) -> Result<Vec<Track>> {
+    let url = api.config.api(&format!("/album?id={}", id));
+    Ok(fetch::<AlbumRep>(api, &url)?.songs)
+}
+
+/// 歌手的热门歌曲
+pub fn get_artist_tracks(api: &Api, id: i64) -> Result<Vec<Track>> {
+    let url = api.config.api(&format!("/artists?id={}", id));
+    Ok(fetch::<ArtistSongsRep>(api, &url)?.hot_songs)
+}
+
+/// 获取封面缩略图, `size` 为边长, 由服务器缩放, 优先读取磁盘缓存
+pub fn get_cover(api: &Api, url: &str, size: u32) -> Result<RgbaImage> {
+    if let Some(img) = cover::load(&api.config, url, size) {
+        return Ok(img);
+    }
+    let bytes = api.client.get(thumb_url(url, size)?).send()?.error_for_status()?.bytes()?;
+    let img = image::load_from_memory(&bytes)?.to_rgba8();
+    let _ = cover::save(&api.config, url, size, &img);
+    Ok(img)
+}
 
-pub fn get_lyric_by_music_id(id: &i64, app: &App) -> Result<Lyric> {
-    let url = format!("{}/lyric?id={}", base_url(), id);
-    let res = app.client.get(&url).send()?;
-    let lyric_rp = res.json::<LyricRep>()?;
+/// 缩略图地址, 封面地址可能已经带有查询参数
+fn thumb_url(url: &str, size: u32) -> Result<Url> {
+    let mut thumb = Url::parse(url).map_err(|_| ApiError::InvalidUrl(url.to_string()))?;
+    thumb
+        .query_pairs_mut()
+        .append_pair("param", &format!("{}y{}", size, size));
+    Ok(thumb)
+}
+
+pub fn get_lyric_by_music_id(id: &i64, api: &Api) -> Result<Lyric> {
+    let url = api.config.api(&format!("/lyric?id={}", id));
+    let lyric_rp = fetch::<LyricRep>(api, &url)?;
     Ok(lyric_rp.lrc)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::{lyric_snippet, thumb_url};
+    use serde_json::json;
+
+    #[test]
+    fn it_picks_the_matching_lyric_line() {
+        let lyrics = json!({ "txt": "第一行\n我们的<b>歌</b>\n最后一行" });
+        assert_eq!(lyric_snippet(&lyrics, "歌"), "我们的歌");
+        let lyrics = json!(["第一行", "第二行"]);
+        assert_eq!(lyric_snippet(&lyrics, "没有"), "第一行");
+    }
+
+    #[test]
+    fn it_appends_the_thumbnail_size() {
+        let url = thumb_url("http://p1.music.126.net/a/1.jpg", 60).unwrap();
+        assert_eq!(url.as_str(), "http://p1.music.126.net/a/1.jpg?param=60y60");
+        let url = thumb_url("http://p1.music.126.net/a/1.jpg?imageView=1", 60).unwrap();
+        assert_eq!(url.as_str(), "http://p1.music.126.net/a/1.jpg?imageView=1&param=60y60");
+    }
+}
@@ -37,3 +37,28 @@ pub fn show_duration(duration: i32) -> String {
         )
     }
 }
+
+/// 格式化文件大小, 如 `12.3 MB`
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// 格式化码率, 如 `320 kbps`
+pub fn format_bitrate(br: i32) -> String {
+    if br >= 999000 {
+        "无损".to_string()
+    } else {
+        format!("{} kbps", br / 1000)
+    }
+}
@@ -0,0 +1,308 @@
+use crate::api_type::api_type::{
+    AccountDetail, Playlist, QrCheckRep, RecommendPlaylist, SearchItem, SearchKind, Track,
+    TrackPage,
+};
+use crate::player::{Media, TrackSource};
+use crate::util::error::Result;
+use crate::util::network::Api;
+use crate::util::LyricRow;
+use crate::Event;
+use crossterm::event::KeyEvent;
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+/// 后台任务的类型, 同类型的任务同时只保留最新的一个
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TaskKind {
+    Login,          // 登录
+    Captcha,        // 发送验证码
+    QrCode,         // 获取登录二维码
+    QrCheck,        // 查询扫码状态
+    Logout,         // 退出登录
+    UserData,       // 喜欢的音乐和歌单
+    PlaylistDetail, // 歌单详情
+    PlaylistPage,   // 歌单中剩下的歌曲
+    PlayingPage,    // 播放列表中剩下的歌曲
+    Play,           // 获取歌曲地址并开始缓冲
+    Download,       // 下载歌曲到本地
+    Seek,           // 跳转播放位置
+    Prefetch,       // 预加载下一首
+    Lyric,          // 歌词
+    Search,         // 搜索
+    Suggest,        // 搜索建议
+    Cover,          // 正在播放的专辑封面
+    PlaylistCover,  // 选中的歌单封面
+    Like,           // 喜欢/取消喜欢歌曲
+    PlaylistEdit,   // 新建、重命名、删除歌单, 添加和移除歌曲
+    PlaylistOrder,  // 保存歌单中歌曲的顺序
+    Recommend,      // 每日推荐
+    Fm,             // 私人 FM 的下一批歌曲
+    FmTrash,        // 私人 FM 中不喜欢的歌曲
+}
+
+impl TaskKind {
+    pub fn desc(&self) -> &'static str {
+        match self {
+            TaskKind::Login => "登录中",
+            TaskKind::Captcha => "发送验证码",
+            TaskKind::QrCode => "获取二维码",
+            TaskKind::QrCheck => "查询扫码状态",
+            TaskKind::Logout => "退出登录",
+            TaskKind::UserData => "加载歌单",
+            TaskKind::PlaylistDetail => "加载歌曲列表",
+            TaskKind::PlaylistPage => "加载更多歌曲",
+            TaskKind::PlayingPage => "加载播放列表",
+            TaskKind::Play => "缓冲中",
+            TaskKind::Download => "下载中",
+            TaskKind::Seek => "跳转中",
+            TaskKind::Prefetch => "预加载下一首",
+            TaskKind::Lyric => "加载歌词",
+            TaskKind::Search => "搜索中",
+            TaskKind::Suggest => "获取搜索建议",
+            TaskKind::Cover => "加载封面",
+            TaskKind::PlaylistCover => "加载歌单封面",
+            TaskKind::Like => "提交喜欢",
+            TaskKind::PlaylistEdit => "保存歌单",
+            TaskKind::PlaylistOrder => "保存歌曲顺序",
+            TaskKind::Recommend => "加载每日推荐",
+            TaskKind::Fm => "加载私人 FM",
+            TaskKind::FmTrash => "提交不喜欢",
+        }
+    }
+
+    /// 是否在界面上显示加载状态
+    pub fn visible(&self) -> bool {
+        !matches!(
+            self,
+            TaskKind::QrCheck
+                | TaskKind::Logout
+                | TaskKind::Lyric
+                | TaskKind::Prefetch
+                | TaskKind::PlayingPage
+                | TaskKind::Suggest
+                | TaskKind::Cover
+                | TaskKind::PlaylistCover
+                | TaskKind::Like
+                | TaskKind::PlaylistOrder
+                | TaskKind::FmTrash
+        )
+    }
+}
+
+/// 后台任务的结果
+pub enum TaskOutput {
+    Account(AccountDetail),
+    CaptchaSent,
+    QrCode {
+        key: String,
+        url: String,
+    },
+    QrCheck(QrCheckRep),
+    LoggedOut,
+    UserData {
+        likelist: Vec<i64>,
+        playlists: Vec<Playlist>,
+        page: TrackPage,
+    },
+    /// 歌曲列表, `id` 为所属的歌单, 搜索结果中的专辑和歌手为空
+    PlaylistDetail {
+        id: Option<i64>,
+        page: TrackPage,
+    },
+    /// 按 id 加载的歌曲, `ids` 为请求的 id
+    PlaylistPage {
+        ids: Vec<i64>,
+        tracks: Vec<Track>,
+    },
+    PlaylistCreated {
+        id: i64,
+        playlists: Vec<Playlist>,
+    },
+    PlaylistRenamed {
+        id: i64,
+        name: String,
+    },
+    PlaylistDeleted(i64),
+    /// 向歌单添加(`add` 为 true)或从歌单移除了歌曲
+    PlaylistTracks {
+        id: i64,
+        track: Track,
+        add: bool,
+    },
+    PlaylistReordered,
+    Recommend {
+        songs: Vec<Track>,
+        playlists: Vec<RecommendPlaylist>,
+    },
+    Fm(Vec<Track>),
+    /// 不喜欢的歌曲名
+    FmTrashed(String),
+    Ready {
+        track: Track,
+        media: Arc<Media>,
+        source: Box<TrackSource>,
+    },
+    Seeked(Box<TrackSource>),
+    Prefetched {
+        id: i64,
+        media: Arc<Media>,
+        source: Box<TrackSource>,
+        lrc: Vec<LyricRow>,
+    },
+    Downloaded {
+        track: Track,
+        path: String,
+    },
+    Lyric {
+        id: i64,
+        rows: Vec<LyricRow>,
+    },
+    Liked {
+        id: i64,
+        like: bool,
+    },
+    Cover {
+        url: String,
+        image: RgbaImage,
+    },
+    Suggest {
+        keywords: String,
+        items: Vec<String>,
+    },
+    Search {
+        kind: SearchKind,
+        page: usize,
+        items: Vec<SearchItem>,
+        total: usize,
+    },
+}
+
+/// 通过事件通道发回主线程的任务结果
+pub struct TaskDone {
+    pub id: u64,
+    pub kind: TaskKind,
+    pub result: Result<TaskOutput>,
+}
+
+/// 任务取消标记, 耗时的任务(如下载)需要定期检查
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 在后台线程执行网络请求, 结果通过 `Event::Task` 发回主线程
+pub struct Worker {
+    runtime: Option<Runtime>,
+    tx: Sender<Event<KeyEvent>>,
+    next_id: u64,
+    running: HashMap<TaskKind, (u64, CancelToken)>,
+}
+
+impl Worker {
+    pub fn new(tx: Sender<Event<KeyEvent>>) -> std::io::Result<Self> {
+        Ok(Self {
+            runtime: Some(Runtime::new()?),
+            tx,
+            next_id: 0,
+            running: HashMap::new(),
+        })
+    }
+
+    /// 提交任务, 同类型的旧任务会被取消
+    pub fn spawn<F>(&mut self, kind: TaskKind, api: Api, task: F)
+    where
+        F: FnOnce(Api, CancelToken) -> Result<TaskOutput> + Send + 'static,
+    {
+        let runtime = match self.runtime.as_ref() {
+            Some(runtime) => runtime,
+            None => return,
+        };
+        self.next_id += 1;
+        let id = self.next_id;
+        let token = CancelToken::default();
+        if let Some((_, old)) = self.running.insert(kind, (id, token.clone())) {
+            old.cancel();
+        }
+        let tx = self.tx.clone();
+        runtime.spawn_blocking(move || {
+            let result = task(api, token.clone());
+            if !token.is_cancelled() {
+                let _ = tx.send(Event::Task(Box::new(TaskDone { id, kind, result })));
+            }
+        });
+    }
+
+    /// 任务完成时调用, 返回结果是否仍然有效(没有被取消或被新任务替换)
+    pub fn finish(&mut self, done: &TaskDone) -> bool {
+        match self.running.get(&done.kind) {
+            Some((id, _)) if *id == done.id => {
+                self.running.remove(&done.kind);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn cancel(&mut self, kind: TaskKind) {
+        if let Some((_, token)) = self.running.remove(&kind) {
+            token.cancel();
+        }
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.running.drain().for_each(|(_, (_, token))| token.cancel());
+    }
+
+    /// 取消界面上显示的任务, 后台任务继续执行
+    pub fn cancel_visible(&mut self) {
+        self.running.retain(|kind, (_, token)| {
+            if kind.visible() {
+                token.cancel();
+            }
+            !kind.visible()
+        });
+    }
+
+    /// 事件通道, 用于其他线程通知主线程
+    pub fn sender(&self) -> Sender<Event<KeyEvent>> {
+        self.tx.clone()
+    }
+
+    pub fn is_running(&self, kind: TaskKind) -> bool {
+        self.running.contains_key(&kind)
+    }
+
+    /// 正在执行且需要显示的任务, 按提交的顺序排列
+    pub fn pending(&self) -> Vec<TaskKind> {
+        let mut pending: Vec<(u64, TaskKind)> = self
+            .running
+            .iter()
+            .filter(|(kind, _)| kind.visible())
+            .map(|(kind, (id, _))| (*id, *kind))
+            .collect();
+        pending.sort_by_key(|(id, _)| *id);
+        pending.into_iter().map(|(_, kind)| kind).collect()
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.cancel_all();
+        // 不等待正在执行的请求, 直接退出
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}